@@ -0,0 +1,55 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+#import bevy_pbr::view_transformations::depth_ndc_to_view_z
+
+struct EyeDomeLighting {
+    strength: f32,
+    // Distance of the sampled neighbours in pixels
+    radius: f32,
+};
+
+// @group(0) @binding(0) is `mesh_view_bindings::view`
+#ifdef MULTISAMPLED
+@group(0) @binding(1) var depth_texture: texture_multisampled_2d<f32>;
+#else
+@group(0) @binding(1) var depth_texture: texture_2d<f32>;
+#endif
+@group(0) @binding(2) var color_texture: texture_2d<f32>;
+@group(0) @binding(3) var<uniform> settings: EyeDomeLighting;
+
+const NEIGHBOURS: u32 = 8u;
+
+// Depth at a pixel, zero where nothing was drawn
+fn ndc_depth(coord: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    return textureLoad(depth_texture, clamp(coord, vec2(0), size - 1), 0).r;
+}
+
+fn log_depth(ndc: f32) -> f32 {
+    return log2(-depth_ndc_to_view_z(ndc));
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.position.xy);
+    let color = textureLoad(color_texture, coord, 0);
+    let center = ndc_depth(coord);
+
+    // Sum of how much farther this pixel is than its neighbours, as in Potree
+    var response = 0.0;
+    for (var i = 0u; i < NEIGHBOURS; i++) {
+        let angle = 6.2831853 * f32(i) / f32(NEIGHBOURS);
+        let offset = vec2<i32>(round(vec2(cos(angle), sin(angle)) * settings.radius));
+        let neighbour = ndc_depth(coord + offset);
+        if neighbour <= 0.0 {
+            continue;
+        }
+        if center > 0.0 {
+            response += max(0.0, log_depth(center) - log_depth(neighbour));
+        } else {
+            // Background next to geometry, outlines the silhouette
+            response += 100.0;
+        }
+    }
+    let shade = exp(-response / f32(NEIGHBOURS) * 300.0 * settings.strength);
+    return vec4(color.rgb * shade, color.a);
+}
@@ -1,5 +1,11 @@
+#import bevy_pbr::mesh_view_bindings::view
 #import bevy_pbr::view_transformations::position_world_to_clip
 
+// Bits of `PlotUniform::sprite_mode`
+const SPRITE_ENABLED: u32 = 1u;
+const SPRITE_ROUND: u32 = 2u;
+const SPRITE_PIXELS: u32 = 4u;
+
 struct Vertex {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
@@ -7,25 +13,165 @@ struct Vertex {
 
     @location(3) i_pos_scale: vec4<f32>,
     @location(4) i_color: vec4<f32>,
+    // Bit 0 marks selected instances, bits 1 to 27 the occupied neighbours
+    @location(5) i_flags: u32,
+};
+
+struct PlotUniform {
+    // Position and scale of the highlighted instance
+    highlight_instance: vec4<f32>,
+    // Color of the highlighted instance, zero alpha disables the highlight
+    highlight_color: vec4<f32>,
+    // Color blended over selected instances by its alpha
+    selection_color: vec4<f32>,
+    // Clipping planes as normal and distance, instances must lie on the positive side
+    clip_planes: array<vec4<f32>, 6>,
+    // Center plane of the slab
+    slab_plane: vec4<f32>,
+    clip_count: u32,
+    // Negative if slab mode is off
+    slab_half_thickness: f32,
+    // Sprite bits, zero draws the mesh
+    sprite_mode: u32,
+    // Edge length of sprites in world units or pixels
+    sprite_size: f32,
+    // Pixel size of instances with scale one, zero keeps world sizes
+    pixel_size: f32,
+    min_pixels: f32,
+    max_pixels: f32,
+    // Largest edge of the mesh, zero if voxels aren't sized in pixels
+    mesh_size: f32,
+    // Darkening of fully occluded corners, zero disables ambient occlusion
+    occlusion_strength: f32,
 };
 
+@group(3) @binding(0) var<uniform> plot: PlotUniform;
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    // Position on the sprite quad, from 0 to 1
+    @location(1) uv: vec2<f32>,
 };
 
+fn is_clipped(position: vec3<f32>) -> bool {
+    for (var i = 0u; i < plot.clip_count; i++) {
+        let plane = plot.clip_planes[i];
+        if dot(plane.xyz, position) < plane.w {
+            return true;
+        }
+    }
+    return plot.slab_half_thickness >= 0.0
+        && abs(dot(plot.slab_plane.xyz, position) - plot.slab_plane.w) > plot.slab_half_thickness;
+}
+
+// Pixels per world unit at the depth of a clip position
+fn pixels_per_unit(clip: vec4<f32>) -> f32 {
+    return view.clip_from_view[1][1] * view.viewport.w * 0.5 / clip.w;
+}
+
+// Applies the pixel size and the pixel clamps to the projected size of an instance
+fn screen_size(pixels: f32, scale: f32) -> f32 {
+    var size = pixels;
+    if plot.pixel_size > 0.0 {
+        size = plot.pixel_size * scale;
+    }
+    return clamp(size, plot.min_pixels, plot.max_pixels);
+}
+
+// Clip position of a corner of the camera facing quad of an instance
+fn sprite_clip_position(vertex: Vertex) -> vec4<f32> {
+    let scale = vertex.i_pos_scale.w;
+    let clip = position_world_to_clip(vertex.i_pos_scale.xyz);
+    var pixels = plot.sprite_size * scale;
+    if (plot.sprite_mode & SPRITE_PIXELS) == 0u {
+        pixels *= pixels_per_unit(clip);
+    }
+    let offset = vertex.position.xy * screen_size(pixels, scale) * 2.0 / view.viewport.zw * clip.w;
+    return clip + vec4(offset, 0.0, 0.0);
+}
+
+// Scale of the mesh of an instance, resolved from pixels if voxels are sized on screen
+fn mesh_scale(vertex: Vertex) -> f32 {
+    let scale = vertex.i_pos_scale.w;
+    if plot.mesh_size <= 0.0 {
+        return scale;
+    }
+    let unit = pixels_per_unit(position_world_to_clip(vertex.i_pos_scale.xyz));
+    return screen_size(plot.mesh_size * scale * unit, scale) / (plot.mesh_size * unit);
+}
+
+// Whether the neighbour at an offset of -1, 0 or 1 cells along each axis is occupied
+fn occupied(flags: u32, offset: vec3<i32>) -> f32 {
+    let index = u32((offset.x + 1) + 3 * (offset.y + 1) + 9 * (offset.z + 1));
+    return f32((flags >> (index + 1u)) & 1u);
+}
+
+// Brightness of a vertex from the three cells in front of its corner of the face, like
+// Minecraft's smooth lighting
+fn ambient_occlusion(vertex: Vertex) -> f32 {
+    let normal = abs(vertex.normal);
+    let corner = vec3<i32>(sign(vertex.position));
+    var face = vec3<i32>(0);
+    var side_a = vec3<i32>(0);
+    var side_b = vec3<i32>(0);
+    if normal.x >= normal.y && normal.x >= normal.z {
+        face.x = i32(sign(vertex.normal.x));
+        side_a.y = corner.y;
+        side_b.z = corner.z;
+    } else if normal.y >= normal.z {
+        face.y = i32(sign(vertex.normal.y));
+        side_a.x = corner.x;
+        side_b.z = corner.z;
+    } else {
+        face.z = i32(sign(vertex.normal.z));
+        side_a.x = corner.x;
+        side_b.y = corner.y;
+    }
+    let a = occupied(vertex.i_flags, face + side_a);
+    let b = occupied(vertex.i_flags, face + side_b);
+    var occlusion = a + b + occupied(vertex.i_flags, face + side_a + side_b);
+    if a + b == 2.0 {
+        occlusion = 3.0;
+    }
+    return 1.0 - plot.occlusion_strength * occlusion / 3.0;
+}
+
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
-    // NOTE: Assumes vertex positions are real world positions
-    let world_position = vertex.position * vertex.i_pos_scale.w + vertex.i_pos_scale.xyz;
-
     var out: VertexOutput;
-    out.clip_position = position_world_to_clip(world_position);
+    if is_clipped(vertex.i_pos_scale.xyz) {
+        // Outside of the clip volume, so the whole instance is culled
+        out.clip_position = vec4(0.0, 0.0, 2.0, 1.0);
+        out.color = vec4(0.0);
+        return out;
+    }
+    if (plot.sprite_mode & SPRITE_ENABLED) != 0u {
+        out.clip_position = sprite_clip_position(vertex);
+        out.uv = vertex.position.xy + 0.5;
+    } else {
+        // NOTE: Assumes vertex positions are real world positions
+        let world_position = vertex.position * mesh_scale(vertex) + vertex.i_pos_scale.xyz;
+        out.clip_position = position_world_to_clip(world_position);
+    }
     out.color = vertex.i_color;
+    if plot.occlusion_strength > 0.0 && (plot.sprite_mode & SPRITE_ENABLED) == 0u {
+        out.color = vec4(out.color.rgb * ambient_occlusion(vertex), out.color.a);
+    }
+    if (vertex.i_flags & 1u) != 0u {
+        let selection = plot.selection_color;
+        out.color = vec4(mix(out.color.rgb, selection.rgb, selection.a), max(out.color.a, selection.a));
+    }
+    if plot.highlight_color.a > 0.0 && all(vertex.i_pos_scale == plot.highlight_instance) {
+        out.color = plot.highlight_color;
+    }
     return out;
 }
 
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (plot.sprite_mode & SPRITE_ROUND) != 0u && length(in.uv - 0.5) > 0.5 {
+        discard;
+    }
     return in.color;
 }
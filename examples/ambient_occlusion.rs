@@ -0,0 +1,68 @@
+//! Opaque voxel terrain shaded by ambient occlusion from neighbouring voxels.
+//!
+//! O toggles the ambient occlusion.
+
+use bevy::prelude::*;
+use bevy_voxel_plot::{
+    Colormap, InstanceData, InstanceMaterialData, VoxelAmbientOcclusion, VoxelMaterialPlugin,
+    VoxelPlotCamera,
+};
+
+const SIZE: usize = 48;
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let edge = 1.0 / SIZE as f32;
+    let mut instances = Vec::new();
+    for x in 0..SIZE {
+        for z in 0..SIZE {
+            let (u, v) = (x as f32 * edge, z as f32 * edge);
+            let height =
+                0.25 + 0.12 * ((u * 9.0).sin() + (v * 7.0).cos()) + 0.1 * (u * v * 20.0).sin();
+            let top = (height / edge) as usize;
+            for y in 0..=top {
+                let color = LinearRgba::from(Colormap::Viridis.sample(y as f32 * edge * 1.8));
+                instances.push(InstanceData {
+                    position: [u - 0.5, y as f32 * edge - 0.25, v - 0.5],
+                    scale: 1.0,
+                    color: [color.red, color.green, color.blue, 1.0],
+                });
+            }
+        }
+    }
+
+    // Voxels fill the grid, so the mesh's edge is the grid spacing.
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(edge))),
+        InstanceMaterialData { instances },
+        VoxelAmbientOcclusion::new(0.7),
+    ));
+
+    commands.spawn(VoxelPlotCamera::default());
+}
+
+fn occlusion_control(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    plots: Query<(Entity, Has<VoxelAmbientOcclusion>), With<InstanceMaterialData>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyO) {
+        return;
+    }
+    for (entity, enabled) in &plots {
+        if enabled {
+            commands.entity(entity).remove::<VoxelAmbientOcclusion>();
+        } else {
+            commands
+                .entity(entity)
+                .insert(VoxelAmbientOcclusion::new(0.7));
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, VoxelMaterialPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, occlusion_control)
+        .run();
+}
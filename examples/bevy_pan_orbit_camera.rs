@@ -8,7 +8,9 @@ use bevy::prelude::{
 };
 use bevy::DefaultPlugins;
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
-use bevy_voxel_plot::{InstanceData, InstanceMaterialData, VoxelMaterialPlugin};
+use bevy_voxel_plot::{
+    Decimation, InstanceData, InstanceMaterialData, VoxelAxes, VoxelBudget, VoxelMaterialPlugin,
+};
 
 fn jet_colormap(value: f32) -> (f32, f32, f32) {
     let four_value = 4.0 * value;
@@ -58,29 +60,13 @@ fn generate_dummy_data() -> (Vec<InstanceData>, f32, f32, f32) {
 fn voxel_plot_setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
     let (instances, cube_width, cube_height, cube_depth) = generate_dummy_data();
 
-    let mut instances: Vec<InstanceData> = instances.into_iter().collect();
-
-    // Sort by opacity (color alpha channel) descending
-    instances.sort_by(|a, b| {
-        b.color[3]
-            .partial_cmp(&a.color[3])
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-
-    // Truncate to top 2 million most opaque points, more than that is usually not responsive
-    const MAX_INSTANCES: usize = 2_000_000;
-    if instances.len() > MAX_INSTANCES {
-        instances.truncate(MAX_INSTANCES);
-    }
-
-    if instances.len() == MAX_INSTANCES {
-        let threshold = instances.last().unwrap().color[3];
-        println!("Auto threshold for opacity was: {}", threshold);
-    }
-
     commands.spawn((
         Mesh3d(meshes.add(Cuboid::new(cube_width, cube_height, cube_depth))),
         InstanceMaterialData { instances },
+        // Draw at most the 2 million most opaque voxels, more than that is usually not responsive
+        VoxelBudget::new(2_000_000, Decimation::Opacity),
+        // Frame the data with gridlines and labelled ticks
+        VoxelAxes::default(),
         // NOTE: Frustum culling is done based on the Aabb of the Mesh and the GlobalTransform.
         // As the cube is at the origin, if its Aabb moves outside the view frustum, all the
         // instanced cubes will be culled.
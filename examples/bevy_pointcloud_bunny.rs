@@ -2,13 +2,12 @@ use bevy::app::{App, Startup};
 use bevy::asset::Assets;
 use bevy::camera::visibility::NoFrustumCulling;
 use bevy::color::{Color, LinearRgba};
-use bevy::math::Vec3;
-use bevy::prelude::{
-    AmbientLight, ColorToComponents, Commands, Cuboid, Mesh, Mesh3d, ResMut, Transform,
-};
+use bevy::prelude::{AmbientLight, ColorToComponents, Commands, Cuboid, Mesh, Mesh3d, ResMut};
 use bevy::DefaultPlugins;
-use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
-use bevy_voxel_plot::{InstanceData, InstanceMaterialData, VoxelMaterialPlugin};
+use bevy_voxel_plot::{
+    Decimation, InstanceData, InstanceMaterialData, VoxelBudget, VoxelMaterialPlugin,
+    VoxelPlotCamera,
+};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
@@ -74,29 +73,11 @@ fn voxel_plot_setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
     let (instances, cube_width, cube_height, cube_depth) =
         load_pcd_file(Path::new("assets/files/bunny.pcd"));
 
-    let mut instances: Vec<InstanceData> = instances.into_iter().collect();
-
-    // Sort by opacity (color alpha channel) descending
-    instances.sort_by(|a, b| {
-        b.color[3]
-            .partial_cmp(&a.color[3])
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-
-    // Truncate to top 2 million most opaque points, more than that is usually not responsive
-    const MAX_INSTANCES: usize = 2_000_000;
-    if instances.len() > MAX_INSTANCES {
-        instances.truncate(MAX_INSTANCES);
-    }
-
-    if instances.len() == MAX_INSTANCES {
-        let threshold = instances.last().unwrap().color[3];
-        println!("Auto threshold for opacity was: {}", threshold);
-    }
-
     commands.spawn((
         Mesh3d(meshes.add(Cuboid::new(cube_width, cube_height, cube_depth))),
         InstanceMaterialData { instances },
+        // Draw at most the 2 million most opaque voxels, more than that is usually not responsive
+        VoxelBudget::new(2_000_000, Decimation::Opacity),
         // NOTE: Frustum culling is done based on the Aabb of the Mesh and the GlobalTransform.
         // As the cube is at the origin, if its Aabb moves outside the view frustum, all the
         // instanced cubes will be culled.
@@ -114,14 +95,11 @@ fn voxel_plot_setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
     });
 
     // camera
-    commands.spawn((
-        Transform::from_translation(Vec3::new(1.0, 0.0, 1.0)),
-        PanOrbitCamera::default(),
-    ));
+    commands.spawn(VoxelPlotCamera::default());
 }
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, VoxelMaterialPlugin, PanOrbitCameraPlugin))
+        .add_plugins((DefaultPlugins, VoxelMaterialPlugin))
         .add_systems(Startup, voxel_plot_setup)
         .run();
 }
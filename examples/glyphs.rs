@@ -0,0 +1,55 @@
+//! One plot mixing cubes, spheres and tetrahedra, chosen per instance.
+
+use bevy::prelude::*;
+use bevy_voxel_plot::{
+    InstanceData, InstanceMaterialData, VoxelGlyphs, VoxelMaterialPlugin, VoxelPlotCamera,
+};
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let size = 10;
+    let edge = 1.0 / size as f32;
+    let mut instances = Vec::new();
+    let mut ids = Vec::new();
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let t = Vec3::new(x as f32, y as f32, z as f32) / (size - 1) as f32;
+                instances.push(InstanceData {
+                    position: (t - 0.5).to_array(),
+                    scale: 1.0,
+                    color: LinearRgba::new(t.x, t.y, t.z, 0.5).to_f32_array(),
+                });
+                // The glyph shows in which third of the x range a voxel lies.
+                ids.push((x * 3 / size) as u8);
+            }
+        }
+    }
+
+    // Glyph meshes share the vertex attributes of the plot's mesh, which is used for
+    // instances without a glyph.
+    let glyphs = vec![
+        meshes.add(Cuboid::from_length(0.6 * edge)),
+        meshes.add(Sphere::new(0.4 * edge)),
+        meshes.add(Mesh::from(Tetrahedron::default()).scaled_by(Vec3::splat(edge))),
+    ];
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(0.6 * edge))),
+        InstanceMaterialData { instances },
+        VoxelGlyphs::new(glyphs, ids),
+    ));
+
+    commands.spawn(AmbientLight {
+        color: Color::WHITE,
+        brightness: 2.0,
+        affects_lightmapped_meshes: false,
+    });
+
+    commands.spawn(VoxelPlotCamera::default());
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, VoxelMaterialPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
@@ -0,0 +1,64 @@
+//! Renders a voxel plot without a window and saves it as `voxel_plot.png`.
+//!
+//! Run with `--fallback` to render on a software adapter, e.g. in CI.
+
+use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy_voxel_plot::{
+    headless_plugins, InstanceData, InstanceMaterialData, OffscreenFrame, OffscreenRender,
+    ViewPreset, VoxelMaterialPlugin, VoxelPlotCamera,
+};
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let size = 20;
+    let mut instances = Vec::with_capacity(size * size * size);
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let t = Vec3::new(x as f32, y as f32, z as f32) / (size - 1) as f32;
+                instances.push(InstanceData {
+                    position: (t - 0.5).to_array(),
+                    scale: 1.0,
+                    color: LinearRgba::new(t.x, t.y, t.z, 0.1).to_f32_array(),
+                });
+            }
+        }
+    }
+
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(1.0 / size as f32))),
+        InstanceMaterialData { instances },
+    ));
+
+    commands.spawn(AmbientLight {
+        color: Color::WHITE,
+        brightness: 2.0,
+        affects_lightmapped_meshes: false,
+    });
+
+    // The camera renders into an image of the given resolution, is saved once the plot is
+    // fitted and exits the app afterwards.
+    commands
+        .spawn((
+            VoxelPlotCamera::default().with_preset(ViewPreset::Isometric),
+            OffscreenRender::new(1280, 720)
+                .with_background(Color::WHITE)
+                .with_path("voxel_plot.png")
+                .with_exit(),
+        ))
+        .observe(|frame: On<OffscreenFrame>| {
+            let bytes = frame.image.data.as_ref().map_or(0, Vec::len);
+            info!("Read back {bytes} bytes");
+        });
+}
+
+fn main() {
+    let settings = WgpuSettings {
+        force_fallback_adapter: std::env::args().any(|arg| arg == "--fallback"),
+        ..default()
+    };
+    App::new()
+        .add_plugins((headless_plugins(settings), VoxelMaterialPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
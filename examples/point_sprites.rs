@@ -0,0 +1,105 @@
+//! A spiral of half a million points drawn as round sprites instead of cubes.
+//!
+//! S switches between round and square sprites, P between pixel and world sizes, C toggles a
+//! clamp of world sizes to 1 to 6 pixels and E toggles eye-dome lighting.
+
+use bevy::camera::visibility::NoFrustumCulling;
+use bevy::prelude::*;
+use bevy_voxel_plot::{
+    Colormap, EyeDomeLighting, InstanceData, InstanceMaterialData, SpriteShape, SpriteSize,
+    VoxelMaterialPlugin, VoxelPixelSize, VoxelPlotCamera, VoxelSprites,
+};
+use std::f32::consts::TAU;
+
+const POINTS: u32 = 500_000;
+
+/// Deterministic pseudo random number in `0..1`.
+fn hash(i: u32, seed: u32) -> f32 {
+    let mut x = i.wrapping_mul(0x9e37_79b9) ^ seed.wrapping_mul(0x85eb_ca6b);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x as f32 / u32::MAX as f32
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let instances = (0..POINTS)
+        .map(|i| {
+            let radius = hash(i, 1).sqrt();
+            let arm = (hash(i, 2) * 3.0).floor() / 3.0;
+            let angle = TAU * (arm + radius * 0.8) + 0.4 * (hash(i, 3) - 0.5);
+            let height = 0.05 * (hash(i, 4) - 0.5) * (1.0 - radius);
+            let color = LinearRgba::from(Colormap::Hot.sample(1.0 - radius * 0.8));
+            InstanceData {
+                position: [radius * angle.cos(), height, radius * angle.sin()],
+                scale: 1.0,
+                color: [color.red, color.green, color.blue, 0.3],
+            }
+        })
+        .collect();
+
+    // The mesh is only used for picking and bounds, the sprites are drawn as quads.
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(0.004))),
+        InstanceMaterialData { instances },
+        VoxelSprites::pixels(SpriteShape::Round, 3.0),
+        NoFrustumCulling,
+    ));
+
+    commands.spawn(VoxelPlotCamera::default());
+}
+
+fn sprite_controls(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut sprites: Query<(Entity, &mut VoxelSprites, Has<VoxelPixelSize>)>,
+) {
+    for (entity, mut sprites, clamped) in &mut sprites {
+        if keys.just_pressed(KeyCode::KeyC) {
+            if clamped {
+                commands.entity(entity).remove::<VoxelPixelSize>();
+            } else {
+                commands
+                    .entity(entity)
+                    .insert(VoxelPixelSize::clamped(1.0, 6.0));
+            }
+        }
+        if keys.just_pressed(KeyCode::KeyS) {
+            sprites.shape = match sprites.shape {
+                SpriteShape::Round => SpriteShape::Square,
+                SpriteShape::Square => SpriteShape::Round,
+            };
+        }
+        if keys.just_pressed(KeyCode::KeyP) {
+            sprites.size = match sprites.size {
+                SpriteSize::Pixels(_) => SpriteSize::World(0.004),
+                SpriteSize::World(_) => SpriteSize::Pixels(3.0),
+            };
+        }
+    }
+}
+
+fn eye_dome_lighting_control(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    cameras: Query<(Entity, Has<EyeDomeLighting>), With<VoxelPlotCamera>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+    for (entity, enabled) in &cameras {
+        if enabled {
+            commands.entity(entity).remove::<EyeDomeLighting>();
+        } else {
+            commands.entity(entity).insert(EyeDomeLighting::default());
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, VoxelMaterialPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (sprite_controls, eye_dome_lighting_control))
+        .run();
+}
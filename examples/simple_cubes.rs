@@ -1,6 +1,5 @@
 use bevy::prelude::*;
-use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
-use bevy_voxel_plot::{InstanceData, InstanceMaterialData, VoxelMaterialPlugin};
+use bevy_voxel_plot::{InstanceData, InstanceMaterialData, VoxelMaterialPlugin, VoxelPlotCamera};
 
 fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
     // Two cubes: one red (alpha 1.0), one blue (alpha 0.5)
@@ -32,16 +31,13 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
         affects_lightmapped_meshes: false,
     });
 
-    // Camera
-    commands.spawn((
-        Transform::from_xyz(1.5, 1.0, 1.5).looking_at(Vec3::ZERO, Vec3::Y),
-        PanOrbitCamera::default(),
-    ));
+    // Camera, fitted to the plot and orbiting around its centre
+    commands.spawn(VoxelPlotCamera::default());
 }
 
 fn main() {
     App::new()
-        .add_plugins((DefaultPlugins, VoxelMaterialPlugin, PanOrbitCameraPlugin))
+        .add_plugins((DefaultPlugins, VoxelMaterialPlugin))
         .add_systems(Startup, setup)
         .run();
 }
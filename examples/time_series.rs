@@ -0,0 +1,75 @@
+//! Plays back a wave travelling through a voxel field, one frame per timestep.
+//!
+//! Space pauses and resumes, the arrow keys step through the frames while paused.
+
+use bevy::prelude::*;
+use bevy_voxel_plot::{
+    Colormap, InstanceData, VoxelFrame, VoxelMaterialPlugin, VoxelPlotCamera, VoxelTimeSeries,
+};
+use std::f32::consts::TAU;
+
+const SIZE: usize = 24;
+const FRAMES: usize = 20;
+
+/// Field of a wave at a phase, colored by value with the opacity growing with the value.
+fn wave_frame(phase: f32) -> Vec<InstanceData> {
+    let mut instances = Vec::with_capacity(SIZE * SIZE * SIZE);
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let p = Vec3::new(x as f32, y as f32, z as f32) / (SIZE - 1) as f32 - 0.5;
+                let value = 0.5 + 0.5 * (p.length() * TAU * 2.0 - phase).sin();
+                let color = LinearRgba::from(Colormap::Viridis.sample(value));
+                instances.push(InstanceData {
+                    position: p.to_array(),
+                    scale: 1.0,
+                    color: [color.red, color.green, color.blue, 0.02 + 0.2 * value],
+                });
+            }
+        }
+    }
+    instances
+}
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let frames =
+        (0..FRAMES).map(|frame| VoxelFrame::from(wave_frame(TAU * frame as f32 / FRAMES as f32)));
+
+    // Only the shown frame is uploaded, colors are blended between frames.
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(1.0 / SIZE as f32))),
+        VoxelTimeSeries::new(frames, 10.0)
+            .with_interpolation()
+            .playing(),
+    ));
+
+    commands.spawn(AmbientLight {
+        color: Color::WHITE,
+        brightness: 2.0,
+        affects_lightmapped_meshes: false,
+    });
+
+    commands.spawn(VoxelPlotCamera::default());
+}
+
+fn playback_controls(keys: Res<ButtonInput<KeyCode>>, mut series: Query<&mut VoxelTimeSeries>) {
+    for mut series in &mut series {
+        if keys.just_pressed(KeyCode::Space) {
+            series.toggle();
+        }
+        if keys.just_pressed(KeyCode::ArrowRight) {
+            series.step(1);
+        }
+        if keys.just_pressed(KeyCode::ArrowLeft) {
+            series.step(-1);
+        }
+    }
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, VoxelMaterialPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, playback_controls)
+        .run();
+}
@@ -0,0 +1,58 @@
+//! Renders a turntable animation of a voxel plot without a window into `turntable/`.
+//!
+//! Run with `--fallback` to render on a software adapter, e.g. in CI. The numbered frames can
+//! be combined into a video, e.g. with
+//! `ffmpeg -framerate 30 -i turntable/frame_%04d.png turntable.mp4`.
+
+use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy_voxel_plot::{
+    headless_plugins, FrameSequence, InstanceData, InstanceMaterialData, OffscreenRender,
+    VoxelMaterialPlugin, VoxelPlotCamera,
+};
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let size = 20;
+    let mut instances = Vec::with_capacity(size * size * size);
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let t = Vec3::new(x as f32, y as f32, z as f32) / (size - 1) as f32;
+                instances.push(InstanceData {
+                    position: (t - 0.5).to_array(),
+                    scale: 1.0,
+                    color: LinearRgba::new(t.x, t.y, t.z, 0.1).to_f32_array(),
+                });
+            }
+        }
+    }
+
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_length(1.0 / size as f32))),
+        InstanceMaterialData { instances },
+    ));
+
+    commands.spawn(AmbientLight {
+        color: Color::WHITE,
+        brightness: 2.0,
+        affects_lightmapped_meshes: false,
+    });
+
+    // One turn around the plot in 90 frames at 30 frames per second, exiting after the last frame.
+    commands.spawn((
+        VoxelPlotCamera::default(),
+        OffscreenRender::new(640, 480).with_exit(),
+        FrameSequence::turntable(90, 30.0, "turntable"),
+    ));
+}
+
+fn main() {
+    let settings = WgpuSettings {
+        force_fallback_adapter: std::env::args().any(|arg| arg == "--fallback"),
+        ..default()
+    };
+    App::new()
+        .add_plugins((headless_plugins(settings), VoxelMaterialPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
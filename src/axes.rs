@@ -0,0 +1,281 @@
+//! Axis frame, gridlines, ticks and labels around a plot.
+
+use crate::bounds::InstanceBounds;
+use bevy::prelude::*;
+use bevy::ui::Val2;
+
+/// Draws an axis aligned frame around the data bounds of a plot, with gridlines on the faces
+/// facing away from the camera and labelled nice-number ticks.
+///
+/// Lines are drawn with gizmos, labels are UI text placed over the camera's view.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct VoxelAxes {
+    /// Titles of the x, y and z axis.
+    pub titles: [String; 3],
+    /// Units of the x, y and z axis, appended to the titles in brackets.
+    pub units: [Option<String>; 3],
+    /// Approximate number of ticks per axis.
+    pub ticks: usize,
+    /// Bounds of the frame, the bounds of the instances are used if `None`.
+    pub bounds: Option<(Vec3, Vec3)>,
+    /// Camera the gridlines are oriented to and the labels are shown in, the active 3D camera
+    /// rendered last if `None`.
+    pub camera: Option<Entity>,
+    pub frame_color: Color,
+    pub grid_color: Color,
+    pub label_color: Color,
+    pub font_size: f32,
+}
+
+impl Default for VoxelAxes {
+    fn default() -> Self {
+        Self {
+            titles: ["x".into(), "y".into(), "z".into()],
+            units: [None, None, None],
+            ticks: 5,
+            bounds: None,
+            camera: None,
+            frame_color: Color::srgb(0.6, 0.6, 0.6),
+            grid_color: Color::srgba(0.6, 0.6, 0.6, 0.3),
+            label_color: Color::WHITE,
+            font_size: 14.0,
+        }
+    }
+}
+
+impl VoxelAxes {
+    pub fn with_titles(
+        mut self,
+        x: impl Into<String>,
+        y: impl Into<String>,
+        z: impl Into<String>,
+    ) -> Self {
+        self.titles = [x.into(), y.into(), z.into()];
+        self
+    }
+
+    pub fn with_units(
+        mut self,
+        x: impl Into<String>,
+        y: impl Into<String>,
+        z: impl Into<String>,
+    ) -> Self {
+        self.units = [Some(x.into()), Some(y.into()), Some(z.into())];
+        self
+    }
+
+    pub fn with_bounds(mut self, min: Vec3, max: Vec3) -> Self {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// Title of an axis including its unit.
+    pub fn label(&self, axis: usize) -> String {
+        match &self.units[axis] {
+            Some(unit) => format!("{} [{unit}]", self.titles[axis]),
+            None => self.titles[axis].clone(),
+        }
+    }
+}
+
+/// UI text of a tick label or axis title.
+#[derive(Component)]
+pub(crate) struct AxisLabel {
+    plot: Entity,
+    index: usize,
+}
+
+/// Ticks at multiples of 1, 2 or 5 times a power of ten within `min..=max`, with their step.
+pub(crate) fn nice_ticks(min: f32, max: f32, count: usize) -> (Vec<f32>, f32) {
+    let range = max - min;
+    if range <= 0.0 || !range.is_finite() || count == 0 {
+        return (vec![min], 1.0);
+    }
+    let raw = range / count as f32;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|&step| step >= raw)
+        .unwrap_or(10.0 * magnitude);
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    ((first..=last).map(|i| i as f32 * step).collect(), step)
+}
+
+/// Formats a tick with as many decimals as the step needs.
+pub(crate) fn format_tick(value: f32, step: f32) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{value:.decimals$}")
+}
+
+/// Draws the frame and gridlines and places the labels of every plot with `VoxelAxes`.
+#[allow(clippy::type_complexity)]
+pub(crate) fn draw_voxel_axes(
+    mut commands: Commands,
+    plots: Query<(Entity, &VoxelAxes, Option<&InstanceBounds>)>,
+    cameras: Query<(Entity, &Camera, &GlobalTransform), With<Camera3d>>,
+    mut labels: Query<(
+        Entity,
+        &AxisLabel,
+        &mut Text,
+        &mut TextFont,
+        &mut TextColor,
+        &mut Node,
+        &mut Visibility,
+        Option<&UiTargetCamera>,
+    )>,
+    mut gizmos: Gizmos,
+) {
+    let mut placed = Vec::new();
+    for (plot, axes, instance_bounds) in &plots {
+        let Some((min, max)) = axes
+            .bounds
+            .or(instance_bounds.map(|bounds| (bounds.min, bounds.max)))
+        else {
+            continue;
+        };
+        if !min.cmple(max).all() {
+            continue;
+        }
+        let camera = match axes.camera {
+            Some(camera) => cameras.get(camera).ok(),
+            None => cameras
+                .iter()
+                .filter(|(_, camera, _)| camera.is_active)
+                .max_by_key(|(_, camera, _)| camera.order),
+        };
+        let Some((camera_entity, camera, camera_transform)) = camera else {
+            continue;
+        };
+
+        let center = (min + max) * 0.5;
+        let eye = camera_transform.translation();
+        let diagonal = (max - min).length();
+        // Sides of the box facing away from the camera, per axis.
+        let back = Vec3::select(eye.cmplt(center), max, min);
+        let front = Vec3::select(eye.cmplt(center), min, max);
+
+        gizmos.cube(
+            Transform::from_translation(center).with_scale(max - min),
+            axes.frame_color,
+        );
+
+        let ticks = [0, 1, 2].map(|axis| nice_ticks(min[axis], max[axis], axes.ticks));
+        let mut texts = Vec::new();
+        for axis in 0..3 {
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            // Gridlines on the back face perpendicular to `axis`.
+            for (along, across) in [(b, c), (c, b)] {
+                for &tick in &ticks[along].0 {
+                    let mut start = Vec3::ZERO;
+                    start[axis] = back[axis];
+                    start[along] = tick;
+                    start[across] = min[across];
+                    let mut end = start;
+                    end[across] = max[across];
+                    gizmos.line(start, end, axes.grid_color);
+                }
+            }
+
+            // Ticks and labels along the lower of the two outline edges parallel to `axis`.
+            let edge = [(front[b], back[c]), (back[b], front[c])]
+                .into_iter()
+                .map(|(edge_b, edge_c)| {
+                    let mut edge = center;
+                    edge[b] = edge_b;
+                    edge[c] = edge_c;
+                    edge
+                })
+                .max_by(|p, q| {
+                    let height = |point: &Vec3| {
+                        camera
+                            .world_to_viewport(camera_transform, *point)
+                            .map_or(f32::NEG_INFINITY, |viewport| viewport.y)
+                    };
+                    height(p).total_cmp(&height(q))
+                })
+                .unwrap_or(center);
+            let mut outward = edge - center;
+            outward[axis] = 0.0;
+            let outward = outward.normalize_or_zero() * diagonal;
+            let (values, step) = &ticks[axis];
+            for &tick in values {
+                let mut point = edge;
+                point[axis] = tick;
+                gizmos.line(point, point + outward * 0.02, axes.frame_color);
+                texts.push((format_tick(tick, *step), point + outward * 0.05));
+            }
+            texts.push((axes.label(axis), edge + outward * 0.12));
+        }
+
+        for (index, (text, position)) in texts.into_iter().enumerate() {
+            let viewport = camera.world_to_viewport(camera_transform, position).ok();
+            placed.push((plot, index));
+            let existing = labels
+                .iter_mut()
+                .find(|(_, label, ..)| label.plot == plot && label.index == index);
+            let Some((
+                entity,
+                _,
+                mut label_text,
+                mut font,
+                mut color,
+                mut node,
+                mut visibility,
+                target,
+            )) = existing
+            else {
+                commands.spawn((
+                    AxisLabel { plot, index },
+                    Text::new(text),
+                    TextFont::from_font_size(axes.font_size),
+                    TextColor(axes.label_color),
+                    label_node(viewport.unwrap_or_default()),
+                    UiTransform::from_translation(Val2::percent(-50.0, -50.0)),
+                    UiTargetCamera(camera_entity),
+                    if viewport.is_some() {
+                        Visibility::Inherited
+                    } else {
+                        Visibility::Hidden
+                    },
+                ));
+                continue;
+            };
+            if label_text.0 != text {
+                label_text.0 = text;
+            }
+            if font.font_size != axes.font_size {
+                font.font_size = axes.font_size;
+            }
+            color.set_if_neq(TextColor(axes.label_color));
+            if let Some(viewport) = viewport {
+                node.set_if_neq(label_node(viewport));
+                visibility.set_if_neq(Visibility::Inherited);
+            } else {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+            if target.is_none_or(|target| target.0 != camera_entity) {
+                commands
+                    .entity(entity)
+                    .insert(UiTargetCamera(camera_entity));
+            }
+        }
+    }
+
+    for (entity, label, ..) in &labels {
+        if !placed.contains(&(label.plot, label.index)) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn label_node(position: Vec2) -> Node {
+    Node {
+        position_type: PositionType::Absolute,
+        left: Val::Px(position.x),
+        top: Val::Px(position.y),
+        ..default()
+    }
+}
@@ -7,12 +7,47 @@
 //! implementation using Bevy's low level rendering API.
 //! It's generally recommended to try the built-in instancing before going with this approach.
 
+use crate::axes::draw_voxel_axes;
+use crate::bounds::update_instance_bounds;
+use crate::budget::update_voxel_budget;
+use crate::camera::update_voxel_plot_cameras;
+use crate::chunks::InstanceChunks;
+use crate::colorbar::update_voxel_colorbars;
+use crate::eye_dome::{
+    configure_eye_dome_lighting_views, prepare_eye_dome_lighting_pipelines, EyeDomeLightingLabel,
+    EyeDomeLightingNode, EyeDomeLightingPipeline, EYE_DOME_SHADER_HANDLE,
+};
+use crate::glyphs::{extract_instance_glyphs, GlyphDraw, InstanceGlyphs};
+use crate::hover::update_voxel_hover;
+use crate::lod::update_voxel_lod;
+use crate::occlusion::update_voxel_ambient_occlusion;
+use crate::offscreen::update_offscreen_renders;
+use crate::picking::{pick_voxels, update_instance_bvh};
+use crate::selection::{extract_instance_flags, InstanceFlags};
+use crate::sequence::update_frame_sequences;
+use crate::slices::update_voxel_slices;
+use crate::sprites::SPRITE_MESH;
+use crate::stream::{extract_instance_streams, prepare_instance_streams, ExtractedInstanceStream};
+use crate::time_series::update_voxel_time_series;
+use crate::uniforms::{
+    extract_plot_uniforms, plot_bind_group_layout, prepare_plot_bind_group, PlotUniform,
+    SetPlotBindGroup,
+};
+use crate::{
+    EyeDomeLighting, InstanceCache, InstanceCacheLoader, InstanceStream, VoxelHover, VoxelSprites,
+};
 use bevy::asset::{load_internal_asset, uuid_handle};
 use bevy::mesh::{MeshVertexBufferLayoutRef, VertexBufferLayout};
 use bevy::pbr::SetMeshViewBindingArrayBindGroup;
 use bevy::render::RenderSystems;
+use bevy::tasks::ComputeTaskPool;
+use bevy::transform::TransformSystems;
+use bevy::ui::UiSystems;
 use bevy::{
-    core_pipeline::core_3d::Transparent3d,
+    core_pipeline::core_3d::{
+        graph::{Core3d, Node3d},
+        Transparent3d,
+    },
     ecs::{
         query::QueryItem,
         system::{lifetimeless::*, SystemParamItem},
@@ -22,38 +57,69 @@ use bevy::{
     },
     prelude::*,
     render::{
-        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        extract_component::{ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin},
         mesh::{allocator::MeshAllocator, RenderMesh, RenderMeshBufferInfo},
         render_asset::RenderAssets,
+        render_graph::{RenderGraphExt, ViewNodeRunner},
         render_phase::{
             AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
             RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
         },
         render_resource::*,
-        renderer::RenderDevice,
-        sync_world::MainEntity,
+        renderer::{RenderDevice, RenderQueue},
+        sync_component::SyncComponentPlugin,
+        sync_world::{MainEntity, RenderEntity},
         view::ExtractedView,
-        Render, RenderApp,
+        Extract, ExtractSchedule, Render, RenderApp,
     },
 };
 use bytemuck::{Pod, Zeroable};
 
 /// Component holding per-instance data for custom rendering.
-#[derive(Component)]
+///
+/// Plots larger than a single GPU buffer are split into spatial chunks automatically.
+#[derive(Component, Clone)]
 pub struct InstanceMaterialData {
     /// A list of per-instance transform and color data.
     pub instances: Vec<InstanceData>,
 }
 
-impl ExtractComponent for InstanceMaterialData {
-    type QueryData = &'static InstanceMaterialData;
-    type QueryFilter = ();
-    type Out = Self;
+/// Subset of a plot's instances chosen for drawing, which is extracted in place of the full
+/// `InstanceMaterialData` while present.
+#[derive(Component)]
+pub(crate) struct ReducedInstances {
+    pub(crate) instances: Vec<InstanceData>,
+    /// Index of each drawn instance in `InstanceMaterialData`, `u32::MAX` for aggregated voxels.
+    pub(crate) indices: Vec<u32>,
+}
 
-    fn extract_component(item: QueryItem<'_, '_, Self::QueryData>) -> Option<Self> {
-        Some(InstanceMaterialData {
-            instances: item.instances.clone(),
-        })
+/// Extracts instance data to the render world, copying it only when it changed.
+#[allow(clippy::type_complexity)]
+fn extract_instance_material_data(
+    mut commands: Commands,
+    query: Extract<
+        Query<(
+            RenderEntity,
+            Ref<InstanceMaterialData>,
+            Option<Ref<ReducedInstances>>,
+        )>,
+    >,
+    extracted: Query<(), With<InstanceMaterialData>>,
+) {
+    for (entity, instance_data, reduced) in &query {
+        let changed = instance_data.is_changed()
+            || reduced.as_ref().is_some_and(|reduced| reduced.is_changed())
+            || !extracted.contains(entity);
+        if !changed {
+            continue;
+        }
+        let instances = match reduced {
+            Some(reduced) => reduced.instances.clone(),
+            None => instance_data.instances.clone(),
+        };
+        commands
+            .entity(entity)
+            .insert(InstanceMaterialData { instances });
     }
 }
 
@@ -63,17 +129,94 @@ pub struct VoxelMaterialPlugin;
 pub const SHADER_HANDLE: Handle<Shader> = uuid_handle!("123e4567-e89b-12d3-a456-426614174000");
 impl Plugin for VoxelMaterialPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(ExtractComponentPlugin::<InstanceMaterialData>::default());
-        app.add_plugins(ExtractComponentPlugin::<CameraPosition>::default()); // Add this line
+        app.add_plugins(SyncComponentPlugin::<InstanceMaterialData>::default());
+        app.add_plugins(ExtractComponentPlugin::<SortView>::default());
+        app.add_plugins(SyncComponentPlugin::<InstanceStream>::default());
+        app.add_plugins(ExtractComponentPlugin::<VoxelSprites>::default());
+        app.add_plugins((
+            ExtractComponentPlugin::<EyeDomeLighting>::default(),
+            UniformComponentPlugin::<EyeDomeLighting>::default(),
+        ));
+        app.add_systems(
+            PostUpdate,
+            (update_voxel_lod, update_voxel_budget)
+                .chain()
+                .after(TransformSystems::Propagate),
+        );
+        app.add_systems(
+            PostUpdate,
+            (update_instance_bvh, (pick_voxels, update_voxel_hover))
+                .chain()
+                .after(TransformSystems::Propagate),
+        );
+        app.add_systems(
+            PostUpdate,
+            (
+                update_voxel_time_series,
+                (update_instance_bounds, update_voxel_ambient_occlusion),
+                update_voxel_plot_cameras,
+            )
+                .chain()
+                .before(TransformSystems::Propagate),
+        );
+        app.add_systems(
+            PostUpdate,
+            draw_voxel_axes.after(TransformSystems::Propagate),
+        );
+        app.add_systems(
+            PostUpdate,
+            update_voxel_slices.before(TransformSystems::Propagate),
+        );
+        app.add_systems(
+            PostUpdate,
+            update_voxel_colorbars.before(UiSystems::Prepare),
+        );
+        #[cfg(feature = "egui")]
+        app.add_systems(Last, crate::egui_widget::update_voxel_plot_views);
+        app.add_systems(
+            Last,
+            (update_frame_sequences, update_offscreen_renders).chain(),
+        );
+        app.init_resource::<VoxelHover>();
+        app.add_plugins(UniformComponentPlugin::<PlotUniform>::default());
+        app.init_asset::<InstanceCache>()
+            .init_asset_loader::<InstanceCacheLoader>();
 
         app.sub_app_mut(RenderApp)
             .add_render_command::<Transparent3d, DrawCustom>()
             .init_resource::<SpecializedMeshPipelines<CustomPipeline>>()
+            .init_resource::<SpecializedRenderPipelines<EyeDomeLightingPipeline>>()
+            .add_systems(
+                ExtractSchedule,
+                (
+                    extract_instance_material_data,
+                    extract_instance_streams,
+                    extract_plot_uniforms,
+                    extract_instance_flags,
+                    extract_instance_glyphs,
+                ),
+            )
             .add_systems(
                 Render,
                 (
                     queue_custom.in_set(RenderSystems::QueueMeshes),
                     prepare_instance_buffers.in_set(RenderSystems::PrepareResources),
+                    prepare_instance_streams.in_set(RenderSystems::PrepareResources),
+                    prepare_plot_bind_group.in_set(RenderSystems::PrepareBindGroups),
+                    configure_eye_dome_lighting_views.in_set(RenderSystems::ManageViews),
+                    prepare_eye_dome_lighting_pipelines.in_set(RenderSystems::Prepare),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<EyeDomeLightingNode>>(
+                Core3d,
+                EyeDomeLightingLabel,
+            )
+            .add_render_graph_edges(
+                Core3d,
+                (
+                    Node3d::EndMainPass,
+                    EyeDomeLightingLabel,
+                    Node3d::StartMainPassPostProcessing,
                 ),
             );
         load_internal_asset!(
@@ -82,15 +225,27 @@ impl Plugin for VoxelMaterialPlugin {
             "../assets/shaders/instancing.wgsl",
             Shader::from_wgsl
         );
+        load_internal_asset!(
+            app,
+            EYE_DOME_SHADER_HANDLE,
+            "../assets/shaders/eye_dome_lighting.wgsl",
+            Shader::from_wgsl
+        );
     }
 
     fn finish(&self, app: &mut App) {
-        app.sub_app_mut(RenderApp).init_resource::<CustomPipeline>();
+        let _ = app
+            .world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .insert(SPRITE_MESH.id(), Rectangle::new(1.0, 1.0).into());
+        app.sub_app_mut(RenderApp)
+            .init_resource::<CustomPipeline>()
+            .init_resource::<EyeDomeLightingPipeline>();
     }
 }
 
 /// Single instance data containing position, scale and color.
-#[derive(Clone, Copy, Pod, Zeroable)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
 #[repr(C)]
 pub struct InstanceData {
     /// (x, y, z) position
@@ -101,8 +256,8 @@ pub struct InstanceData {
     pub color: [f32; 4],
 }
 
-/// Queues custom rendering commands for entities with `InstanceMaterialData`.
-#[allow(clippy::too_many_arguments)]
+/// Queues custom rendering commands for entities with `InstanceMaterialData` or `InstanceStream`.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 fn queue_custom(
     transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
     custom_pipeline: Res<CustomPipeline>,
@@ -110,13 +265,16 @@ fn queue_custom(
     pipeline_cache: Res<PipelineCache>,
     meshes: Res<RenderAssets<RenderMesh>>,
     render_mesh_instances: Res<RenderMeshInstances>,
-    material_meshes: Query<(Entity, &MainEntity), With<InstanceMaterialData>>,
+    material_meshes: Query<
+        (Entity, &MainEntity, Has<VoxelSprites>),
+        Or<(With<InstanceMaterialData>, With<ExtractedInstanceStream>)>,
+    >,
     mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
-    views: Query<(&ExtractedView, &Msaa)>,
+    views: Query<(&ExtractedView, &Msaa, Has<EyeDomeLighting>)>,
 ) {
     let draw_custom = transparent_3d_draw_functions.read().id::<DrawCustom>();
 
-    for (view, msaa) in &views {
+    for (view, msaa, eye_dome_lighting) in &views {
         let Some(transparent_phase) = transparent_render_phases.get_mut(&view.retained_view_entity)
         else {
             continue;
@@ -126,17 +284,25 @@ fn queue_custom(
         let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
         let rangefinder = view.rangefinder3d();
 
-        for (entity, main_entity) in &material_meshes {
+        for (entity, main_entity, sprites) in &material_meshes {
             let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(*main_entity)
             else {
                 continue;
             };
-            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+            let mesh_id = if sprites {
+                SPRITE_MESH.id()
+            } else {
+                mesh_instance.mesh_asset_id
+            };
+            let Some(mesh) = meshes.get(mesh_id) else {
                 continue;
             };
 
-            let key =
-                view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+            let key = CustomPipelineKey {
+                mesh_key: view_key
+                    | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology()),
+                depth: eye_dome_lighting,
+            };
             let pipeline = pipelines
                 .specialize(&pipeline_cache, &custom_pipeline, key, &mesh.layout)
                 .unwrap();
@@ -154,74 +320,231 @@ fn queue_custom(
 }
 
 /// GPU buffer holding instance data ready for rendering.
+pub(crate) struct InstanceBuffer {
+    pub(crate) buffer: Buffer,
+    /// Per-instance flags in the same order, see `InstanceFlags`.
+    pub(crate) flags: Buffer,
+    /// Instance ranges drawn with each glyph mesh, in order.
+    pub(crate) draws: Vec<GlyphDraw>,
+}
+
+/// Instance buffers of a plot, drawn in order.
+#[derive(Component)]
+pub(crate) struct InstanceBuffers(pub(crate) Vec<InstanceBuffer>);
+
+/// Largest number of instances stored in a single chunk, if the device allows buffers this big.
+const MAX_CHUNK_INSTANCES: usize = 1 << 20;
+
+/// Spatial chunks of a plot together with their GPU buffers.
 #[derive(Component)]
-struct InstanceBuffer {
-    buffer: Buffer,
-    length: usize,
+struct PlotChunks {
+    chunks: InstanceChunks,
+    buffers: Vec<Buffer>,
+    flag_buffers: Vec<Buffer>,
+    /// Whether the flag buffers were written since they were created or cleared.
+    flagged: bool,
 }
 
-#[derive(Component, Clone)]
-struct CameraPosition(Vec3);
+/// Creates a zero initialized buffer for `len` per-instance values of `T`.
+pub(crate) fn create_instance_buffer<T>(
+    render_device: &RenderDevice,
+    label: &'static str,
+    len: usize,
+) -> Buffer {
+    render_device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: (len * size_of::<T>()) as u64,
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Active 3D camera the instances are sorted back to front for.
+#[derive(Component, Clone, Copy)]
+pub(crate) struct SortView {
+    position: Vec3,
+    forward: Vec3,
+    orthographic: bool,
+}
 
-impl ExtractComponent for CameraPosition {
-    type QueryData = &'static GlobalTransform;
+impl SortView {
+    /// Sort key growing with the distance from the viewer: the squared distance to the camera
+    /// for perspective projections, the depth along the view direction for orthographic ones,
+    /// where all view rays are parallel.
+    fn key(&self, position: Vec3) -> f32 {
+        if self.orthographic {
+            (position - self.position).dot(self.forward)
+        } else {
+            self.position.distance_squared(position)
+        }
+    }
+
+    /// Whether points above `value` along `axis` are behind the points below it.
+    pub(crate) fn above_is_farther(&self, axis: usize, value: f32) -> bool {
+        if self.orthographic {
+            self.forward[axis] > 0.0
+        } else {
+            self.position[axis] < value
+        }
+    }
+}
+
+impl Default for SortView {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            forward: Vec3::NEG_Z,
+            orthographic: false,
+        }
+    }
+}
+
+impl ExtractComponent for SortView {
+    type QueryData = (
+        &'static Camera,
+        &'static GlobalTransform,
+        &'static Projection,
+    );
     type QueryFilter = With<Camera3d>;
     type Out = Self;
 
-    fn extract_component(transform: QueryItem<'_, '_, Self::QueryData>) -> Option<Self> {
-        Some(CameraPosition(transform.translation()))
+    fn extract_component(
+        (camera, transform, projection): QueryItem<'_, '_, Self::QueryData>,
+    ) -> Option<Self> {
+        camera.is_active.then(|| SortView {
+            position: transform.translation(),
+            forward: transform.forward().as_vec3(),
+            orthographic: matches!(projection, Projection::Orthographic(_)),
+        })
     }
 }
 
-/// Prepares instance buffers each frame, sorting instances by distance to camera.
+/// Prepares instance buffers each frame, sorting chunks and the instances within them back to
+/// front for the camera's projection.
+#[allow(clippy::type_complexity)]
 fn prepare_instance_buffers(
     mut commands: Commands,
-    query: Query<(Entity, &InstanceMaterialData)>,
+    mut query: Query<(
+        Entity,
+        Ref<InstanceMaterialData>,
+        Option<&InstanceFlags>,
+        Option<&InstanceGlyphs>,
+        Option<&mut PlotChunks>,
+    )>,
     render_device: Res<RenderDevice>,
-    camera_query: Query<&CameraPosition>,
+    render_queue: Res<RenderQueue>,
+    views: Query<&SortView>,
 ) {
-    let camera_pos = camera_query
-        .iter()
-        .next()
-        .map(|pos| pos.0)
-        .unwrap_or(Vec3::ZERO);
+    let view = views.iter().next().copied().unwrap_or_default();
 
-    // // Debug: Print camera position
-    // println!("Camera position: {:?}", camera_pos);
+    let max_chunk_len = (render_device.limits().max_buffer_size as usize
+        / size_of::<InstanceData>())
+    .min(MAX_CHUNK_INSTANCES);
 
-    for (entity, instance_data) in &query {
+    for (entity, instance_data, flags, glyphs, plot_chunks) in &mut query {
         if instance_data.instances.is_empty() {
-            commands.entity(entity).remove::<InstanceBuffer>();
+            commands
+                .entity(entity)
+                .remove::<(InstanceBuffers, PlotChunks)>();
             continue;
         }
 
-        let mut sorted_instances = instance_data.instances.clone();
-        sorted_instances.sort_by(|a, b| {
-            let dist_a = camera_pos.distance_squared(Vec3::from_slice(&a.position));
-            let dist_b = camera_pos.distance_squared(Vec3::from_slice(&b.position));
-            dist_b
-                .partial_cmp(&dist_a)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
+        let mut rebuilt = None;
+        let plot_chunks = match plot_chunks {
+            Some(plot_chunks) if !instance_data.is_changed() => plot_chunks.into_inner(),
+            _ => {
+                let chunks = InstanceChunks::new(&instance_data.instances, max_chunk_len);
+                let buffers = chunks
+                    .chunks
+                    .iter()
+                    .map(|chunk| {
+                        create_instance_buffer::<InstanceData>(
+                            &render_device,
+                            "instance data buffer",
+                            chunk.len(),
+                        )
+                    })
+                    .collect();
+                let flag_buffers = chunks
+                    .chunks
+                    .iter()
+                    .map(|chunk| {
+                        create_instance_buffer::<u32>(
+                            &render_device,
+                            "instance flags buffer",
+                            chunk.len(),
+                        )
+                    })
+                    .collect();
+                rebuilt.insert(PlotChunks {
+                    chunks,
+                    buffers,
+                    flag_buffers,
+                    flagged: false,
+                })
+            }
+        };
 
-        // Debug: Print instance order and distances
-        // println!("Sorted instances (far to near):");
-        // for inst in &sorted_instances {
-        //     let pos = Vec3::from_slice(&inst.pos_scale[0..3]);
-        //     let dist = camera_pos.distance(pos);
-        //     println!("  pos: {:?}, distance: {:.3}, color: {:?}", pos, dist, inst.color);
-        // }
-
-        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-            label: Some("instance data buffer"),
-            contents: bytemuck::cast_slice(sorted_instances.as_slice()),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        let instances = &instance_data.instances;
+        let flags = flags.map(|flags| &flags.0);
+        let sorted = ComputeTaskPool::get().scope(|scope| {
+            for chunk in &plot_chunks.chunks.chunks {
+                scope.spawn(async move {
+                    let mut keyed: Vec<(f32, u32)> = chunk
+                        .iter()
+                        .map(|&i| {
+                            let position = Vec3::from_array(instances[i as usize].position);
+                            (view.key(position), i)
+                        })
+                        .collect();
+                    keyed.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+                    let draws = match glyphs {
+                        Some(glyphs) => glyphs.arrange(&mut keyed),
+                        None => GlyphDraw::plot_mesh(keyed.len()),
+                    };
+                    let sorted: Vec<InstanceData> =
+                        keyed.iter().map(|&(_, i)| instances[i as usize]).collect();
+                    let sorted_flags: Option<Vec<u32>> =
+                        flags.map(|flags| keyed.iter().map(|&(_, i)| flags[i as usize]).collect());
+                    (sorted, sorted_flags, draws)
+                });
+            }
         });
 
-        commands.entity(entity).insert(InstanceBuffer {
-            buffer,
-            length: sorted_instances.len(),
-        });
+        let clear_flags = flags.is_none() && plot_chunks.flagged;
+        plot_chunks.flagged = flags.is_some();
+        let instance_buffers = plot_chunks
+            .chunks
+            .back_to_front(&view)
+            .into_iter()
+            .map(|index| {
+                let (chunk, chunk_flags, draws) = &sorted[index];
+                let buffer = &plot_chunks.buffers[index];
+                let flag_buffer = &plot_chunks.flag_buffers[index];
+                render_queue.write_buffer(buffer, 0, bytemuck::cast_slice(chunk));
+                if let Some(chunk_flags) = chunk_flags {
+                    render_queue.write_buffer(flag_buffer, 0, bytemuck::cast_slice(chunk_flags));
+                } else if clear_flags {
+                    render_queue.write_buffer(
+                        flag_buffer,
+                        0,
+                        bytemuck::cast_slice(&vec![0u32; chunk.len()]),
+                    );
+                }
+                InstanceBuffer {
+                    buffer: buffer.clone(),
+                    flags: flag_buffer.clone(),
+                    draws: draws.clone(),
+                }
+            })
+            .collect();
+
+        commands
+            .entity(entity)
+            .insert(InstanceBuffers(instance_buffers));
+        if let Some(plot_chunks) = rebuilt {
+            commands.entity(entity).insert(plot_chunks);
+        }
     }
 }
 
@@ -245,23 +568,35 @@ impl FromWorld for CustomPipeline {
     }
 }
 
+/// Key of the voxel pipeline.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct CustomPipelineKey {
+    mesh_key: MeshPipelineKey,
+    /// Whether voxels are depth tested and write depth, for eye-dome lighting.
+    depth: bool,
+}
+
 impl SpecializedMeshPipeline for CustomPipeline {
-    type Key = MeshPipelineKey;
+    type Key = CustomPipelineKey;
 
     fn specialize(
         &self,
         key: Self::Key,
         layout: &MeshVertexBufferLayoutRef,
     ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
-        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+        let mut descriptor = self.mesh_pipeline.specialize(key.mesh_key, layout)?;
 
         let color_format = TextureFormat::Rgba8UnormSrgb;
 
         descriptor.depth_stencil = Some(DepthStencilState {
             format: TextureFormat::Depth32Float,
-            depth_compare: CompareFunction::Always,
+            depth_compare: if key.depth {
+                CompareFunction::GreaterEqual
+            } else {
+                CompareFunction::Always
+            },
             stencil: StencilState::default(),
-            depth_write_enabled: false,
+            depth_write_enabled: key.depth,
             bias: DepthBiasState::default(),
         });
 
@@ -282,6 +617,8 @@ impl SpecializedMeshPipeline for CustomPipeline {
             write_mask: ColorWrites::ALL,
         });
 
+        descriptor.layout.push(plot_bind_group_layout());
+
         descriptor.vertex.shader = self.shader.clone();
         descriptor.vertex.buffers.push(VertexBufferLayout {
             array_stride: size_of::<InstanceData>() as u64,
@@ -299,6 +636,15 @@ impl SpecializedMeshPipeline for CustomPipeline {
                 },
             ],
         });
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: size_of::<u32>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![VertexAttribute {
+                format: VertexFormat::Uint32,
+                offset: 0,
+                shader_location: 5,
+            }],
+        });
 
         descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
         Ok(descriptor)
@@ -311,6 +657,7 @@ type DrawCustom = (
     SetMeshViewBindGroup<0>,
     SetMeshViewBindingArrayBindGroup<1>,
     SetMeshBindGroup<2>,
+    SetPlotBindGroup<3>,
     DrawMeshInstanced,
 );
 
@@ -324,57 +671,80 @@ impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
         SRes<MeshAllocator>,
     );
     type ViewQuery = ();
-    type ItemQuery = Read<InstanceBuffer>;
+    type ItemQuery = (Read<InstanceBuffers>, Has<VoxelSprites>);
 
     #[inline]
     fn render<'w>(
         item: &P,
         _view: (),
-        instance_buffer: Option<&'w InstanceBuffer>,
+        plot: Option<(&'w InstanceBuffers, bool)>,
         (meshes, render_mesh_instances, mesh_allocator): SystemParamItem<'w, '_, Self::Param>,
         pass: &mut TrackedRenderPass<'w>,
     ) -> RenderCommandResult {
         let mesh_allocator = mesh_allocator.into_inner();
+        let meshes = meshes.into_inner();
 
         let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(item.main_entity())
         else {
             return RenderCommandResult::Skip;
         };
-        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+        let Some((instance_buffers, sprites)) = plot else {
             return RenderCommandResult::Skip;
         };
-        let Some(instance_buffer) = instance_buffer else {
-            return RenderCommandResult::Skip;
+        // Sprites replace the plot's mesh and any glyphs.
+        let plot_mesh_id = if sprites {
+            SPRITE_MESH.id()
+        } else {
+            mesh_instance.mesh_asset_id
         };
-        let Some(vertex_buffer_slice) =
-            mesh_allocator.mesh_vertex_slice(&mesh_instance.mesh_asset_id)
-        else {
+        let Some(plot_mesh) = meshes.get(plot_mesh_id) else {
             return RenderCommandResult::Skip;
         };
 
-        pass.set_vertex_buffer(0, vertex_buffer_slice.buffer.slice(..));
-        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
-
-        match &gpu_mesh.buffer_info {
-            RenderMeshBufferInfo::Indexed {
-                index_format,
-                count,
-            } => {
-                let Some(index_buffer_slice) =
-                    mesh_allocator.mesh_index_slice(&mesh_instance.mesh_asset_id)
-                else {
-                    return RenderCommandResult::Skip;
+        for instance_buffer in &instance_buffers.0 {
+            pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+            pass.set_vertex_buffer(2, instance_buffer.flags.slice(..));
+            for draw in &instance_buffer.draws {
+                let mesh_id = match draw.mesh {
+                    Some(mesh) if !sprites => mesh,
+                    _ => plot_mesh_id,
                 };
-
-                pass.set_index_buffer(index_buffer_slice.buffer.slice(..), *index_format);
-                pass.draw_indexed(
-                    index_buffer_slice.range.start..(index_buffer_slice.range.start + count),
-                    vertex_buffer_slice.range.start as i32,
-                    0..instance_buffer.length as u32,
-                );
-            }
-            RenderMeshBufferInfo::NonIndexed => {
-                pass.draw(vertex_buffer_slice.range, 0..instance_buffer.length as u32);
+                let Some(gpu_mesh) = meshes.get(mesh_id) else {
+                    continue;
+                };
+                // Glyphs are drawn with the pipeline specialized for the plot's mesh. Extraction
+                // replaces glyphs that do not fit it, this only guards glyphs not checked yet.
+                if gpu_mesh.layout != plot_mesh.layout
+                    || gpu_mesh.primitive_topology() != plot_mesh.primitive_topology()
+                {
+                    continue;
+                }
+                let Some(vertex_buffer_slice) = mesh_allocator.mesh_vertex_slice(&mesh_id) else {
+                    continue;
+                };
+                pass.set_vertex_buffer(0, vertex_buffer_slice.buffer.slice(..));
+
+                match &gpu_mesh.buffer_info {
+                    RenderMeshBufferInfo::Indexed {
+                        index_format,
+                        count,
+                    } => {
+                        let Some(index_buffer_slice) = mesh_allocator.mesh_index_slice(&mesh_id)
+                        else {
+                            continue;
+                        };
+                        pass.set_index_buffer(index_buffer_slice.buffer.slice(..), *index_format);
+                        pass.draw_indexed(
+                            index_buffer_slice.range.start
+                                ..(index_buffer_slice.range.start + count),
+                            vertex_buffer_slice.range.start as i32,
+                            draw.instances.clone(),
+                        );
+                    }
+                    RenderMeshBufferInfo::NonIndexed => {
+                        pass.draw(vertex_buffer_slice.range.clone(), draw.instances.clone());
+                    }
+                }
             }
         }
         RenderCommandResult::Success
@@ -0,0 +1,56 @@
+//! Bounds of a plot's instances, shared by the axes and the plot camera.
+
+use crate::InstanceMaterialData;
+use bevy::camera::primitives::MeshAabb;
+use bevy::prelude::*;
+
+/// Bounding box of the instances of a plot, updated when the data or the mesh changes.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct InstanceBounds {
+    pub(crate) min: Vec3,
+    pub(crate) max: Vec3,
+}
+
+impl InstanceBounds {
+    pub(crate) fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_instance_bounds(
+    mut commands: Commands,
+    plots: Query<(
+        Entity,
+        Ref<InstanceMaterialData>,
+        Ref<Mesh3d>,
+        Has<InstanceBounds>,
+    )>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (entity, instance_data, mesh, has_bounds) in &plots {
+        if has_bounds && !instance_data.is_changed() && !mesh.is_changed() {
+            continue;
+        }
+        if instance_data.instances.is_empty() {
+            commands.entity(entity).remove::<InstanceBounds>();
+            continue;
+        }
+        let Some(aabb) = meshes.get(&*mesh).and_then(|mesh| mesh.compute_aabb()) else {
+            continue;
+        };
+        let (center, half_extents) = (Vec3::from(aabb.center), Vec3::from(aabb.half_extents));
+        let (min, max) = instance_data.instances.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), instance| {
+                let position = Vec3::from_array(instance.position) + center * instance.scale;
+                let half_extents = half_extents * instance.scale.abs();
+                (
+                    min.min(position - half_extents),
+                    max.max(position + half_extents),
+                )
+            },
+        );
+        commands.entity(entity).insert(InstanceBounds { min, max });
+    }
+}
@@ -0,0 +1,235 @@
+//! Automatic decimation of plots that exceed an instance budget.
+
+use crate::lod::{mesh_edge, LodView};
+use crate::{InstanceData, InstanceMaterialData, ReducedInstances, VoxelLod};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+/// Number of bisection steps used to size the grid of `Decimation::Stratified`.
+const STRATIFIED_STEPS: u32 = 12;
+
+/// How instances are ranked when a plot exceeds its budget.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Decimation {
+    /// Keeps the most opaque instances.
+    #[default]
+    Opacity,
+    /// Keeps the instances that appear largest on screen, re-evaluated when the camera moves.
+    /// Instances behind the camera are dropped.
+    ScreenSize,
+    /// Keeps the most opaque instance in every cell of a uniform grid sized to fit the budget.
+    Stratified,
+}
+
+/// Limits the number of instances drawn for an entity with `InstanceMaterialData`.
+///
+/// The source data is left untouched, only the drawn subset is reduced. Plots with a `VoxelLod`
+/// ignore this component, as the level of detail has its own budget: the level of detail owns
+/// the drawn subset while present, and the budget takes over again once it is removed.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+#[require(VoxelBudgetReport)]
+pub struct VoxelBudget {
+    pub max_instances: usize,
+    pub decimation: Decimation,
+}
+
+impl VoxelBudget {
+    pub fn new(max_instances: usize, decimation: Decimation) -> Self {
+        Self {
+            max_instances,
+            decimation,
+        }
+    }
+}
+
+/// Result of the decimation of a plot with a `VoxelBudget`.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelBudgetReport {
+    /// Number of instances drawn.
+    pub drawn: usize,
+    /// Number of instances in the plot.
+    pub total: usize,
+    /// Effective threshold of the decimation: the lowest kept opacity, the smallest kept
+    /// projected size in pixels or the grid cell size. Zero if no instances were dropped.
+    pub threshold: f32,
+}
+
+/// Indices of the `max` instances with the largest keys in ascending order, and the smallest key
+/// kept.
+fn keep_largest(mut keyed: Vec<(f32, u32)>, max: usize) -> (Vec<u32>, f32) {
+    if keyed.len() > max {
+        keyed.select_nth_unstable_by(max - 1, |a, b| b.0.total_cmp(&a.0));
+        keyed.truncate(max);
+    }
+    let threshold = keyed.iter().map(|k| k.0).reduce(f32::min).unwrap_or(0.0);
+    let mut kept: Vec<u32> = keyed.into_iter().map(|k| k.1).collect();
+    kept.sort_unstable();
+    (kept, threshold)
+}
+
+/// Keeps the most opaque instance per grid cell, searching for the smallest cell size that
+/// stays within the budget.
+fn stratified(instances: &[InstanceData], max: usize) -> (Vec<u32>, f32) {
+    let (min, max_corner) = instances.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), instance| {
+            let position = Vec3::from_array(instance.position);
+            (min.min(position), max.max(position))
+        },
+    );
+    let extent = (max_corner - min).max_element().max(f32::EPSILON);
+
+    let sample = |cell: f32| {
+        let mut best: HashMap<IVec3, u32> = HashMap::default();
+        for (i, instance) in instances.iter().enumerate() {
+            let key = ((Vec3::from_array(instance.position) - min) / cell)
+                .floor()
+                .as_ivec3();
+            best.entry(key)
+                .and_modify(|kept| {
+                    if instance.color[3] > instances[*kept as usize].color[3] {
+                        *kept = i as u32;
+                    }
+                })
+                .or_insert(i as u32);
+        }
+        best
+    };
+
+    // Bisect the cell size in log space between one cell per instance and a single cell.
+    let (mut lo, mut hi) = ((extent / instances.len() as f32).ln(), extent.ln() + 1.0);
+    let mut kept = (sample(hi.exp()), hi.exp());
+    for _ in 0..STRATIFIED_STEPS {
+        let mid = 0.5 * (lo + hi);
+        let cells = sample(mid.exp());
+        if cells.len() <= max {
+            hi = mid;
+            kept = (cells, mid.exp());
+        } else {
+            lo = mid;
+        }
+    }
+
+    let mut indices: Vec<u32> = kept.0.into_values().collect();
+    indices.sort_unstable();
+    (indices, kept.1)
+}
+
+/// Selects the drawn subset of plots exceeding their budget.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_voxel_budget(
+    mut commands: Commands,
+    mut plots: Query<
+        (
+            Entity,
+            &mut InstanceMaterialData,
+            Ref<VoxelBudget>,
+            &VoxelBudgetReport,
+            &Mesh3d,
+            Has<ReducedInstances>,
+        ),
+        Without<VoxelLod>,
+    >,
+    cameras: Query<(&Camera, Ref<GlobalTransform>, Ref<Projection>), With<Camera3d>>,
+    meshes: Res<Assets<Mesh>>,
+    mut removed: RemovedComponents<VoxelBudget>,
+    mut data: Query<(&mut InstanceMaterialData, Has<VoxelLod>), Without<VoxelBudget>>,
+) {
+    for entity in removed.read() {
+        let Ok((mut instance_data, lod)) = data.get_mut(entity) else {
+            continue;
+        };
+        commands.entity(entity).remove::<VoxelBudgetReport>();
+        // The drawn subset of plots with a level of detail is not the budget's to remove.
+        if !lod {
+            commands.entity(entity).remove::<ReducedInstances>();
+            instance_data.set_changed();
+        }
+    }
+
+    let view_changed = cameras
+        .iter()
+        .any(|(_, transform, projection)| transform.is_changed() || projection.is_changed());
+    let view = LodView::from_cameras(cameras.iter().map(|(camera, transform, projection)| {
+        (camera, transform.into_inner(), projection.into_inner())
+    }));
+
+    for (entity, mut instance_data, budget, report, mesh, reduced) in &mut plots {
+        let view_dependent = budget.decimation == Decimation::ScreenSize;
+        let pending = report.total != instance_data.instances.len();
+        let refresh = instance_data.is_changed()
+            || budget.is_changed()
+            || pending
+            || (view_dependent && view_changed);
+        if !refresh {
+            continue;
+        }
+
+        let instances = &instance_data.instances;
+        let total = instances.len();
+        let max = budget.max_instances.max(1);
+        if total <= max {
+            // Plots with a level of detail are not queried, so the drawn subset is the budget's.
+            if reduced {
+                commands.entity(entity).remove::<ReducedInstances>();
+                instance_data.set_changed();
+            }
+            commands.entity(entity).insert(VoxelBudgetReport {
+                drawn: total,
+                total,
+                threshold: 0.0,
+            });
+            continue;
+        }
+
+        let (kept, threshold) = match budget.decimation {
+            Decimation::Opacity => keep_largest(
+                (0..total as u32)
+                    .map(|i| (instances[i as usize].color[3], i))
+                    .collect(),
+                max,
+            ),
+            Decimation::ScreenSize => {
+                let Some(view) = &view else {
+                    continue;
+                };
+                let mesh_edge = mesh_edge(&meshes, mesh);
+                keep_largest(
+                    (0..total as u32)
+                        .filter_map(|i| {
+                            let instance = &instances[i as usize];
+                            let extent = instance.scale * mesh_edge;
+                            let position = Vec3::from_array(instance.position);
+                            // Instances behind the camera are invisible however large.
+                            if view.is_behind(position, 0.5 * extent) {
+                                return None;
+                            }
+                            Some((view.pixels(position, extent, 0.5 * extent), i))
+                        })
+                        .collect(),
+                    max,
+                )
+            }
+            Decimation::Stratified => stratified(instances, max),
+        };
+
+        let new_report = VoxelBudgetReport {
+            drawn: kept.len(),
+            total,
+            threshold,
+        };
+        if !view_dependent && new_report != *report {
+            info!(
+                "Voxel budget: drawing {} of {} instances, {:?} threshold {}",
+                new_report.drawn, total, budget.decimation, threshold
+            );
+        }
+        commands.entity(entity).insert((
+            ReducedInstances {
+                instances: kept.iter().map(|&i| instances[i as usize]).collect(),
+                indices: kept,
+            },
+            new_report,
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+//! Versioned binary cache of `InstanceData` for fast reloading of large datasets.
+//!
+//! The file consists of a 24 byte header (magic, version, flags and instance count, all
+//! little endian) followed by the raw `InstanceData` bytes, optionally zlib compressed.
+
+use crate::InstanceData;
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use bevy::prelude::*;
+use bytemuck::Zeroable;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 8] = *b"VOXPLOT\0";
+const HEADER_SIZE: usize = 24;
+const FLAG_COMPRESSED: u32 = 1;
+
+/// Current version of the instance cache format.
+pub const INSTANCE_CACHE_VERSION: u32 = 1;
+
+/// Errors that can occur while reading an instance cache.
+#[derive(Debug)]
+pub enum InstanceCacheError {
+    Io(std::io::Error),
+    /// The data does not start with the instance cache magic.
+    NotACache,
+    /// The cache was written with an unknown format version.
+    UnsupportedVersion(u32),
+    /// The payload is shorter than the `expected` bytes announced in the header.
+    Truncated {
+        expected: usize,
+    },
+    /// The instance count in the header does not match the payload.
+    InvalidData(String),
+}
+
+impl fmt::Display for InstanceCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceCacheError::Io(err) => write!(f, "io error: {err}"),
+            InstanceCacheError::NotACache => write!(f, "missing instance cache magic"),
+            InstanceCacheError::UnsupportedVersion(version) => {
+                write!(f, "unsupported instance cache version {version}")
+            }
+            InstanceCacheError::Truncated { expected } => {
+                write!(f, "payload is shorter than {expected} bytes")
+            }
+            InstanceCacheError::InvalidData(msg) => write!(f, "invalid data: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for InstanceCacheError {}
+
+impl From<std::io::Error> for InstanceCacheError {
+    fn from(err: std::io::Error) -> Self {
+        InstanceCacheError::Io(err)
+    }
+}
+
+/// Writes instances in the binary cache format, zlib compressed if `compress` is set.
+pub fn write_instance_cache<W: Write>(
+    mut writer: W,
+    instances: &[InstanceData],
+    compress: bool,
+) -> std::io::Result<()> {
+    let flags = if compress { FLAG_COMPRESSED } else { 0 };
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&INSTANCE_CACHE_VERSION.to_le_bytes())?;
+    writer.write_all(&flags.to_le_bytes())?;
+    writer.write_all(&(instances.len() as u64).to_le_bytes())?;
+
+    let payload: &[u8] = bytemuck::cast_slice(instances);
+    if compress {
+        let mut encoder = ZlibEncoder::new(writer, Compression::fast());
+        encoder.write_all(payload)?;
+        encoder.finish()?.flush()
+    } else {
+        writer.write_all(payload)?;
+        writer.flush()
+    }
+}
+
+/// Saves instances to a binary cache file.
+pub fn save_instance_cache(
+    path: impl AsRef<Path>,
+    instances: &[InstanceData],
+    compress: bool,
+) -> std::io::Result<()> {
+    write_instance_cache(BufWriter::new(File::create(path)?), instances, compress)
+}
+
+/// Number of instances read at a time, so that a corrupt count in the header cannot allocate
+/// more memory than the payload holds.
+const READ_CHUNK_INSTANCES: usize = 1 << 16;
+
+/// Parses the header, returning the compression flag and the instance count.
+fn parse_header(header: &[u8]) -> Result<(bool, usize), InstanceCacheError> {
+    if header.len() < HEADER_SIZE || header[..8] != MAGIC {
+        return Err(InstanceCacheError::NotACache);
+    }
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    if version != INSTANCE_CACHE_VERSION {
+        return Err(InstanceCacheError::UnsupportedVersion(version));
+    }
+    let flags = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    let count = u64::from_le_bytes(header[16..24].try_into().unwrap());
+    let invalid = || InstanceCacheError::InvalidData(format!("instance count {count}"));
+    let count = usize::try_from(count).map_err(|_| invalid())?;
+    count
+        .checked_mul(size_of::<InstanceData>())
+        .ok_or_else(invalid)?;
+    Ok((flags & FLAG_COMPRESSED != 0, count))
+}
+
+/// Reads the payload straight into the instance vector, growing it as data arrives rather than
+/// trusting the count in the header.
+fn read_payload<R: Read>(
+    reader: R,
+    compressed: bool,
+    count: usize,
+) -> Result<Vec<InstanceData>, InstanceCacheError> {
+    if compressed {
+        read_instances(ZlibDecoder::new(reader), count)
+    } else {
+        read_instances(reader, count)
+    }
+}
+
+fn read_instances<R: Read>(
+    mut reader: R,
+    count: usize,
+) -> Result<Vec<InstanceData>, InstanceCacheError> {
+    let mut instances = Vec::new();
+    while instances.len() < count {
+        let start = instances.len();
+        let len = (count - start).min(READ_CHUNK_INSTANCES);
+        instances.resize(start + len, InstanceData::zeroed());
+        let payload: &mut [u8] = bytemuck::cast_slice_mut(&mut instances[start..]);
+        match reader.read_exact(payload) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(InstanceCacheError::Truncated {
+                    expected: count * size_of::<InstanceData>(),
+                });
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if reader.read(&mut [0u8])? != 0 {
+        return Err(InstanceCacheError::InvalidData(format!(
+            "payload is longer than {count} instances"
+        )));
+    }
+    Ok(instances)
+}
+
+/// Reads instances from the binary cache format.
+pub fn read_instance_cache<R: Read>(
+    mut reader: R,
+) -> Result<Vec<InstanceData>, InstanceCacheError> {
+    let mut header = [0u8; HEADER_SIZE];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| InstanceCacheError::NotACache)?;
+    let (compressed, count) = parse_header(&header)?;
+    read_payload(reader, compressed, count)
+}
+
+/// Parses instances from an in-memory cache file.
+pub fn parse_instance_cache(bytes: &[u8]) -> Result<Vec<InstanceData>, InstanceCacheError> {
+    let (compressed, count) = parse_header(bytes)?;
+    let payload = &bytes[HEADER_SIZE..];
+    // Checked by `parse_header`, and the length of uncompressed payloads is known upfront.
+    let expected = count * size_of::<InstanceData>();
+    if !compressed && payload.len() != expected {
+        return Err(InstanceCacheError::InvalidData(format!(
+            "{count} instances in {} bytes",
+            payload.len()
+        )));
+    }
+    read_payload(payload, compressed, count)
+}
+
+/// Loads instances from a binary cache file.
+pub fn load_instance_cache(
+    path: impl AsRef<Path>,
+) -> Result<Vec<InstanceData>, InstanceCacheError> {
+    read_instance_cache(BufReader::new(File::open(path)?))
+}
+
+/// Instances loaded from a binary cache file through the `AssetServer`.
+#[derive(Asset, TypePath, Clone)]
+pub struct InstanceCache {
+    pub instances: Vec<InstanceData>,
+}
+
+/// Asset loader for binary instance cache files (`.vxp`).
+#[derive(Default, TypePath)]
+pub struct InstanceCacheLoader;
+
+impl AssetLoader for InstanceCacheLoader {
+    type Asset = InstanceCache;
+    type Settings = ();
+    type Error = InstanceCacheError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(InstanceCache {
+            instances: parse_instance_cache(&bytes)?,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vxp"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instances() -> Vec<InstanceData> {
+        (0..100)
+            .map(|i| InstanceData {
+                position: [i as f32, -(i as f32), 0.5],
+                scale: 1.0 + i as f32,
+                color: [0.1, 0.2, 0.3, i as f32 / 100.0],
+            })
+            .collect()
+    }
+
+    fn cache(instances: &[InstanceData], compress: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_instance_cache(&mut bytes, instances, compress).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        for compress in [false, true] {
+            let bytes = cache(&instances(), compress);
+            for read in [
+                parse_instance_cache(&bytes).unwrap(),
+                read_instance_cache(bytes.as_slice()).unwrap(),
+            ] {
+                assert_eq!(
+                    bytemuck::cast_slice::<_, u8>(&read),
+                    bytemuck::cast_slice::<_, u8>(&instances())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hostile_count() {
+        for compress in [false, true] {
+            for count in [u64::MAX, u64::MAX / 32, 1 << 40] {
+                let mut bytes = cache(&instances(), compress);
+                bytes[16..24].copy_from_slice(&count.to_le_bytes());
+                assert!(parse_instance_cache(&bytes).is_err());
+                assert!(read_instance_cache(bytes.as_slice()).is_err());
+            }
+        }
+    }
+}
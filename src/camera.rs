@@ -0,0 +1,260 @@
+//! Built-in orbit camera for plots, with view presets and fitting to the data bounds.
+
+use crate::bounds::InstanceBounds;
+use bevy::camera::{NormalizedRenderTarget, RenderTarget, ScalingMode};
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_8, PI};
+
+/// Pixels per line for pixel based scrolling.
+const PIXELS_PER_LINE: f32 = 16.0;
+
+/// Views a `VoxelPlotCamera` can snap to, named after the side the camera looks from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewPreset {
+    PosX,
+    NegX,
+    /// Looking down onto the plot.
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+    /// Looking from the `(1, 1, 1)` diagonal.
+    Isometric,
+}
+
+impl ViewPreset {
+    /// Yaw and pitch of the view.
+    fn angles(self) -> (f32, f32) {
+        match self {
+            ViewPreset::PosX => (FRAC_PI_2, 0.0),
+            ViewPreset::NegX => (-FRAC_PI_2, 0.0),
+            ViewPreset::PosY => (0.0, FRAC_PI_2),
+            ViewPreset::NegY => (0.0, -FRAC_PI_2),
+            ViewPreset::PosZ => (0.0, 0.0),
+            ViewPreset::NegZ => (PI, 0.0),
+            ViewPreset::Isometric => (FRAC_PI_4, (1.0 / 3f32.sqrt()).asin()),
+        }
+    }
+}
+
+/// Input collected for a plot camera since it was last updated, in logical pixels and lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct PlotCameraInput {
+    pub(crate) orbit: Vec2,
+    pub(crate) pan: Vec2,
+    pub(crate) zoom: f32,
+}
+
+/// Orbit camera for plots: drag to orbit, drag with the pan button to pan and scroll to zoom.
+///
+/// The camera is fitted to the bounds of the plot once its data is available, with the target at
+/// the plot's centre. Cameras rendering to a window react to the mouse within their viewport,
+/// cameras of a `VoxelPlotView` to the input on the egui widget. Orthographic cameras zoom by
+/// scaling the view instead of moving closer.
+#[derive(Component, Clone, Debug, PartialEq)]
+#[require(Camera3d)]
+pub struct VoxelPlotCamera {
+    /// Point the camera orbits around and looks at.
+    pub target: Vec3,
+    /// Distance from the target.
+    pub radius: f32,
+    /// Rotation around the y axis in radians, zero looks from the positive z axis.
+    pub yaw: f32,
+    /// Elevation above the xz plane in radians.
+    pub pitch: f32,
+    /// Plot the camera is fitted to, the first plot if `None`.
+    pub plot: Option<Entity>,
+    pub orbit_button: MouseButton,
+    pub pan_button: MouseButton,
+    /// Radians per pixel dragged.
+    pub orbit_sensitivity: f32,
+    /// Relative change of the radius per scrolled line.
+    pub zoom_sensitivity: f32,
+    /// Whether the camera reacts to input.
+    pub enabled: bool,
+    fit_requested: bool,
+    dragging: Option<MouseButton>,
+    pub(crate) input: PlotCameraInput,
+}
+
+impl Default for VoxelPlotCamera {
+    fn default() -> Self {
+        let (yaw, pitch) = ViewPreset::Isometric.angles();
+        Self {
+            target: Vec3::ZERO,
+            radius: 5.0,
+            yaw,
+            pitch,
+            plot: None,
+            orbit_button: MouseButton::Left,
+            pan_button: MouseButton::Right,
+            orbit_sensitivity: 0.005,
+            zoom_sensitivity: 0.1,
+            enabled: true,
+            fit_requested: true,
+            dragging: None,
+            input: PlotCameraInput::default(),
+        }
+    }
+}
+
+impl VoxelPlotCamera {
+    pub fn with_plot(mut self, plot: Entity) -> Self {
+        self.plot = Some(plot);
+        self
+    }
+
+    pub fn with_preset(mut self, preset: ViewPreset) -> Self {
+        self.snap_to(preset);
+        self
+    }
+
+    /// Rotates the camera to a preset view, keeping target and distance.
+    pub fn snap_to(&mut self, preset: ViewPreset) {
+        (self.yaw, self.pitch) = preset.angles();
+    }
+
+    /// Moves the target to the centre of the plot and the camera back until the plot fits the
+    /// view, on the next update.
+    pub fn fit(&mut self) {
+        self.fit_requested = true;
+    }
+
+    /// Rotation of the camera.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0)
+    }
+
+    /// Position of the camera.
+    pub fn position(&self) -> Vec3 {
+        self.target + self.rotation() * Vec3::Z * self.radius
+    }
+}
+
+/// Height of the orthographic view at a radius, matching a perspective camera with the
+/// default field of view.
+fn orthographic_height(radius: f32) -> f32 {
+    2.0 * radius * FRAC_PI_8.tan()
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_voxel_plot_cameras(
+    mut cameras: Query<(
+        &mut VoxelPlotCamera,
+        &mut Transform,
+        &mut Projection,
+        &Camera,
+        &RenderTarget,
+    )>,
+    plots: Query<(Entity, &InstanceBounds)>,
+    windows: Query<(Entity, &Window, Has<PrimaryWindow>)>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    motion: Res<AccumulatedMouseMotion>,
+    scroll: Res<AccumulatedMouseScroll>,
+) {
+    let primary = windows
+        .iter()
+        .find_map(|(entity, _, primary)| primary.then_some(entity));
+    let scrolled = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / PIXELS_PER_LINE,
+    };
+
+    for (mut controller, mut transform, mut projection, camera, target) in &mut cameras {
+        let controller = controller.as_mut();
+
+        // Mouse input on windows, only within the camera's viewport.
+        if let Some(NormalizedRenderTarget::Window(window)) = target.normalize(primary) {
+            let inside = windows
+                .get(window.entity())
+                .ok()
+                .and_then(|(_, window, _)| window.cursor_position())
+                .zip(camera.logical_viewport_rect())
+                .is_some_and(|(cursor, viewport)| viewport.contains(cursor));
+            if inside && controller.dragging.is_none() {
+                controller.dragging = [controller.orbit_button, controller.pan_button]
+                    .into_iter()
+                    .find(|&button| mouse.just_pressed(button));
+            }
+            match controller.dragging {
+                Some(button) if !mouse.pressed(button) => controller.dragging = None,
+                Some(button) if button == controller.orbit_button => {
+                    controller.input.orbit += motion.delta
+                }
+                Some(_) => controller.input.pan += motion.delta,
+                None => {}
+            }
+            if inside {
+                controller.input.zoom += scrolled;
+            }
+        }
+        let input = std::mem::take(&mut controller.input);
+
+        if controller.fit_requested {
+            let bounds = match controller.plot {
+                Some(plot) => plots.get(plot).ok(),
+                None => plots.iter().next(),
+            };
+            if let Some((_, bounds)) = bounds {
+                let sphere = (bounds.max - bounds.min).length() * 0.5;
+                controller.target = bounds.center();
+                controller.radius = match &*projection {
+                    Projection::Perspective(perspective) => {
+                        let vertical = perspective.fov * 0.5;
+                        let horizontal = (vertical.tan() * perspective.aspect_ratio).atan();
+                        sphere / vertical.min(horizontal).sin()
+                    }
+                    _ => sphere / FRAC_PI_8.tan(),
+                };
+                controller.fit_requested = false;
+            }
+        }
+
+        if controller.enabled {
+            controller.yaw -= input.orbit.x * controller.orbit_sensitivity;
+            controller.pitch = (controller.pitch + input.orbit.y * controller.orbit_sensitivity)
+                .clamp(-FRAC_PI_2, FRAC_PI_2);
+
+            if input.pan != Vec2::ZERO {
+                let height = match &*projection {
+                    Projection::Perspective(perspective) => {
+                        2.0 * controller.radius * (perspective.fov * 0.5).tan()
+                    }
+                    _ => orthographic_height(controller.radius),
+                };
+                let pixels = camera
+                    .logical_viewport_size()
+                    .map_or(1.0, |size| size.y.max(1.0));
+                let rotation = controller.rotation();
+                controller.target += (rotation * Vec3::NEG_X * input.pan.x
+                    + rotation * Vec3::Y * input.pan.y)
+                    * height
+                    / pixels;
+            }
+
+            controller.radius *= (1.0 - controller.zoom_sensitivity).powf(input.zoom);
+        }
+
+        transform.set_if_neq(
+            Transform::from_translation(controller.position()).with_rotation(controller.rotation()),
+        );
+        // Orthographic cameras zoom by their scale and get a symmetric depth range, so moving
+        // closer does not clip the plot.
+        if let Projection::Orthographic(orthographic) = &*projection {
+            let viewport_height = orthographic_height(controller.radius);
+            let near = -orthographic.far;
+            let current = matches!(
+                orthographic.scaling_mode,
+                ScalingMode::FixedVertical { viewport_height: height } if height == viewport_height
+            );
+            if !current || orthographic.near != near {
+                if let Projection::Orthographic(orthographic) = projection.as_mut() {
+                    orthographic.scaling_mode = ScalingMode::FixedVertical { viewport_height };
+                    orthographic.near = near;
+                }
+            }
+        }
+    }
+}
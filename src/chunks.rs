@@ -0,0 +1,115 @@
+//! Spatial partitioning of large plots into chunks that each fit into a single GPU buffer.
+
+use crate::bevy_voxel_plot::SortView;
+use crate::InstanceData;
+use bevy::math::Vec3;
+
+enum KdNode {
+    Leaf(usize),
+    Split {
+        axis: usize,
+        value: f32,
+        below: usize,
+        above: usize,
+    },
+}
+
+/// Instance indices split into spatially disjoint chunks by a k-d tree.
+pub(crate) struct InstanceChunks {
+    pub(crate) chunks: Vec<Vec<u32>>,
+    nodes: Vec<KdNode>,
+}
+
+impl InstanceChunks {
+    /// Splits the instances at the median of the longest axis until every chunk holds at most
+    /// `max_chunk_len` instances.
+    pub(crate) fn new(instances: &[InstanceData], max_chunk_len: usize) -> Self {
+        let mut chunks = InstanceChunks {
+            chunks: Vec::new(),
+            nodes: Vec::new(),
+        };
+        let indices = (0..instances.len() as u32).collect();
+        chunks.split(instances, indices, max_chunk_len.max(1));
+        chunks
+    }
+
+    fn split(
+        &mut self,
+        instances: &[InstanceData],
+        mut indices: Vec<u32>,
+        max_chunk_len: usize,
+    ) -> usize {
+        let node = self.nodes.len();
+        if indices.len() <= max_chunk_len {
+            self.nodes.push(KdNode::Leaf(self.chunks.len()));
+            self.chunks.push(indices);
+            return node;
+        }
+
+        let (min, max) = indices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &i| {
+                let position = Vec3::from_array(instances[i as usize].position);
+                (min.min(position), max.max(position))
+            },
+        );
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let mid = indices.len() / 2;
+        indices.select_nth_unstable_by(mid, |&a, &b| {
+            instances[a as usize].position[axis].total_cmp(&instances[b as usize].position[axis])
+        });
+        let value = instances[indices[mid] as usize].position[axis];
+        let above = indices.split_off(mid);
+
+        self.nodes.push(KdNode::Split {
+            axis,
+            value,
+            below: 0,
+            above: 0,
+        });
+        let below_node = self.split(instances, indices, max_chunk_len);
+        let above_node = self.split(instances, above, max_chunk_len);
+        if let KdNode::Split { below, above, .. } = &mut self.nodes[node] {
+            *below = below_node;
+            *above = above_node;
+        }
+        node
+    }
+
+    /// Chunk indices ordered from the farthest to the nearest chunk as seen from `view`.
+    pub(crate) fn back_to_front(&self, view: &SortView) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.chunks.len());
+        if !self.nodes.is_empty() {
+            self.visit(0, view, &mut order);
+        }
+        order
+    }
+
+    fn visit(&self, node: usize, view: &SortView, order: &mut Vec<usize>) {
+        match self.nodes[node] {
+            KdNode::Leaf(chunk) => order.push(chunk),
+            KdNode::Split {
+                axis,
+                value,
+                below,
+                above,
+            } => {
+                let (far, near) = if view.above_is_farther(axis, value) {
+                    (above, below)
+                } else {
+                    (below, above)
+                };
+                self.visit(far, view, order);
+                self.visit(near, view, order);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Clipping planes and slab views, evaluated per instance in the shader.
+
+use bevy::prelude::*;
+
+/// Maximum number of clipping planes per plot, further planes are ignored.
+pub const MAX_CLIP_PLANES: usize = 6;
+
+/// Half-space bounded by a plane, containing the points the normal points to.
+///
+/// A plane with a zero normal is disabled and contains every point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipPlane {
+    /// Unit normal of the plane, or zero to disable it.
+    pub normal: Vec3,
+    /// Signed distance of the plane from the origin along the normal.
+    pub distance: f32,
+}
+
+impl ClipPlane {
+    /// Plane through `point`, keeping the side `normal` points to. A zero or non-finite
+    /// `normal` gives a disabled plane.
+    pub fn new(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize_or_zero();
+        Self {
+            normal,
+            distance: normal.dot(point),
+        }
+    }
+
+    /// Whether the plane has a zero normal and clips nothing.
+    pub fn is_disabled(&self) -> bool {
+        self.normal == Vec3::ZERO
+    }
+
+    /// Signed distance of a point from the plane, positive on the kept side and zero for
+    /// disabled planes.
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        if self.is_disabled() {
+            return 0.0;
+        }
+        self.normal.dot(point) - self.distance
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.signed_distance(point) >= 0.0
+    }
+
+    /// Plane as passed to the shader, all zeros for disabled planes so they clip nothing.
+    pub(crate) fn to_vec4(self) -> Vec4 {
+        if self.is_disabled() {
+            return Vec4::ZERO;
+        }
+        self.normal.extend(self.distance)
+    }
+}
+
+/// Thin slice around a plane, the only part of the plot shown in slab mode. A slab around a
+/// disabled plane shows everything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelSlab {
+    /// Center plane of the slab, moved to move the slab.
+    pub plane: ClipPlane,
+    /// Total thickness of the slab in world units.
+    pub thickness: f32,
+}
+
+impl VoxelSlab {
+    pub fn new(point: Vec3, normal: Vec3, thickness: f32) -> Self {
+        Self {
+            plane: ClipPlane::new(point, normal),
+            thickness,
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.plane.is_disabled() || self.plane.signed_distance(point).abs() <= self.thickness * 0.5
+    }
+}
+
+/// Hides the instances of a plot whose position lies outside the clipping planes or the slab.
+///
+/// Clipping happens in the shader, the instance data is left untouched and picking ignores
+/// hidden instances.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct VoxelClip {
+    /// Half-spaces an instance must lie in to be drawn, at most `MAX_CLIP_PLANES`.
+    pub planes: Vec<ClipPlane>,
+    /// Shows only the instances within the slab, if set.
+    pub slab: Option<VoxelSlab>,
+}
+
+impl VoxelClip {
+    /// Axis aligned clipping to the box between `min` and `max`.
+    pub fn from_box(min: Vec3, max: Vec3) -> Self {
+        let planes = [Vec3::X, Vec3::Y, Vec3::Z]
+            .into_iter()
+            .flat_map(|axis| [ClipPlane::new(min, axis), ClipPlane::new(max, -axis)])
+            .collect();
+        Self { planes, slab: None }
+    }
+
+    /// Slab mode, showing only a slice of the given thickness around a plane.
+    pub fn from_slab(slab: VoxelSlab) -> Self {
+        Self {
+            planes: Vec::new(),
+            slab: Some(slab),
+        }
+    }
+
+    pub fn with_plane(mut self, plane: ClipPlane) -> Self {
+        self.planes.push(plane);
+        self
+    }
+
+    pub fn with_slab(mut self, slab: VoxelSlab) -> Self {
+        self.slab = Some(slab);
+        self
+    }
+
+    /// Whether an instance at the position is drawn.
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .take(MAX_CLIP_PLANES)
+            .all(|plane| plane.contains(point))
+            && self.slab.is_none_or(|slab| slab.contains(point))
+    }
+}
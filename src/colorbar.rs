@@ -0,0 +1,190 @@
+//! Colorbar legend of a plot's colormap, drawn as Bevy UI.
+
+use crate::axes::{format_tick, nice_ticks};
+use crate::VoxelColormap;
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::ui::Val2;
+
+/// Texels of the colorbar gradient.
+const GRADIENT_STEPS: u32 = 256;
+
+/// Shows the plot's `VoxelColormap` as a vertical colorbar with ticks and a title.
+///
+/// The colorbar is rebuilt whenever this component or the colormap changes.
+#[derive(Component, Clone, Debug, PartialEq)]
+#[require(VoxelColormap)]
+pub struct VoxelColorbar {
+    pub title: String,
+    /// Approximate number of ticks.
+    pub ticks: usize,
+    /// Camera the colorbar is shown in, the default UI camera if `None`.
+    pub camera: Option<Entity>,
+    /// Layout of the colorbar's root node, by default in the top right corner.
+    pub node: Node,
+    /// Width and height of the bar in logical pixels.
+    pub size: Vec2,
+    pub label_color: Color,
+    pub font_size: f32,
+}
+
+impl Default for VoxelColorbar {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            ticks: 5,
+            camera: None,
+            node: Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(16.0),
+                right: Val::Px(16.0),
+                ..default()
+            },
+            size: Vec2::new(16.0, 200.0),
+            label_color: Color::WHITE,
+            font_size: 14.0,
+        }
+    }
+}
+
+impl VoxelColorbar {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..default()
+        }
+    }
+}
+
+/// Root UI node of a plot's colorbar.
+#[derive(Component)]
+pub(crate) struct ColorbarNode {
+    plot: Entity,
+}
+
+/// Vertical gradient of a colormap, with the top of the window at the top.
+fn gradient_image(colormap: &VoxelColormap) -> Image {
+    let data = (0..GRADIENT_STEPS)
+        .rev()
+        .flat_map(|i| {
+            let t = i as f32 / (GRADIENT_STEPS - 1) as f32;
+            colormap.colormap.sample(t).to_srgba().to_u8_array()
+        })
+        .collect();
+    Image::new(
+        Extent3d {
+            width: 1,
+            height: GRADIENT_STEPS,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_voxel_colorbars(
+    mut commands: Commands,
+    plots: Query<(Entity, Ref<VoxelColorbar>, Ref<VoxelColormap>)>,
+    nodes: Query<(Entity, &ColorbarNode)>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (entity, node) in &nodes {
+        let stale = plots
+            .get(node.plot)
+            .is_ok_and(|(_, colorbar, colormap)| colorbar.is_changed() || colormap.is_changed());
+        if stale || !plots.contains(node.plot) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for (plot, colorbar, colormap) in &plots {
+        let exists = nodes.iter().any(|(_, node)| node.plot == plot);
+        if exists && !colorbar.is_changed() && !colormap.is_changed() {
+            continue;
+        }
+
+        let (min, max) = colormap.window.range();
+        let (ticks, step) = nice_ticks(min, max, colorbar.ticks);
+        let labels: Vec<(f32, String)> = ticks
+            .iter()
+            .map(|&tick| (colormap.window.normalize(tick), format_tick(tick, step)))
+            .collect();
+        let label_width = labels
+            .iter()
+            .map(|(_, label)| label.chars().count())
+            .max()
+            .unwrap_or(0) as f32
+            * colorbar.font_size
+            * 0.6;
+        let font = TextFont::from_font_size(colorbar.font_size);
+        let color = TextColor(colorbar.label_color);
+
+        let mut root = commands.spawn((
+            ColorbarNode { plot },
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(colorbar.font_size * 0.5),
+                ..colorbar.node.clone()
+            },
+        ));
+        if let Some(camera) = colorbar.camera {
+            root.insert(UiTargetCamera(camera));
+        }
+        root.with_children(|root| {
+            if !colorbar.title.is_empty() {
+                root.spawn((Text::new(colorbar.title.clone()), font.clone(), color));
+            }
+            root.spawn(Node {
+                flex_direction: FlexDirection::Row,
+                column_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                row.spawn((
+                    Node {
+                        width: Val::Px(colorbar.size.x),
+                        height: Val::Px(colorbar.size.y),
+                        ..default()
+                    },
+                    ImageNode::new(images.add(gradient_image(&colormap))),
+                ));
+                row.spawn(Node {
+                    width: Val::Px(label_width + 8.0),
+                    height: Val::Px(colorbar.size.y),
+                    ..default()
+                })
+                .with_children(|labels_node| {
+                    for (t, label) in labels {
+                        labels_node
+                            .spawn((
+                                Node {
+                                    position_type: PositionType::Absolute,
+                                    top: Val::Percent((1.0 - t) * 100.0),
+                                    align_items: AlignItems::Center,
+                                    column_gap: Val::Px(4.0),
+                                    ..default()
+                                },
+                                UiTransform::from_translation(Val2::percent(0.0, -50.0)),
+                            ))
+                            .with_children(|tick| {
+                                tick.spawn((
+                                    Node {
+                                        width: Val::Px(4.0),
+                                        height: Val::Px(1.0),
+                                        ..default()
+                                    },
+                                    BackgroundColor(colorbar.label_color),
+                                ));
+                                tick.spawn((Text::new(label), font.clone(), color));
+                            });
+                    }
+                });
+            });
+        });
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+/// Colormaps used to turn scalar values into voxel colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    /// Black to white.
+    #[default]
+    Grayscale,
+    /// Blue to red via cyan and yellow.
+    Jet,
+    /// Perceptually uniform dark blue to yellow.
+    Viridis,
+    /// Black to white via red and yellow.
+    Hot,
+}
+
+const VIRIDIS: [[f32; 3]; 9] = [
+    [0.267, 0.005, 0.329],
+    [0.283, 0.141, 0.458],
+    [0.254, 0.265, 0.530],
+    [0.207, 0.372, 0.553],
+    [0.164, 0.471, 0.558],
+    [0.128, 0.567, 0.551],
+    [0.135, 0.659, 0.518],
+    [0.478, 0.821, 0.3176],
+    [0.993, 0.906, 0.144],
+];
+
+impl Colormap {
+    /// Returns the sRGB color for a value in `0.0..=1.0`, values outside are clamped.
+    pub fn sample(&self, value: f32) -> Color {
+        let t = if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, 1.0)
+        };
+        let [r, g, b] = match self {
+            Colormap::Grayscale => [t, t, t],
+            Colormap::Jet => {
+                let four_value = 4.0 * t;
+                [
+                    (four_value - 1.5).clamp(0.0, 1.0),
+                    (four_value - 0.5).clamp(0.0, 1.0) - (four_value - 2.5).clamp(0.0, 1.0),
+                    1.0 - (four_value - 1.5).clamp(0.0, 1.0),
+                ]
+            }
+            Colormap::Viridis => {
+                let x = t * (VIRIDIS.len() - 1) as f32;
+                let i = (x.floor() as usize).min(VIRIDIS.len() - 2);
+                let f = x - i as f32;
+                let (a, b) = (VIRIDIS[i], VIRIDIS[i + 1]);
+                [
+                    a[0] + (b[0] - a[0]) * f,
+                    a[1] + (b[1] - a[1]) * f,
+                    a[2] + (b[2] - a[2]) * f,
+                ]
+            }
+            Colormap::Hot => [
+                (3.0 * t).clamp(0.0, 1.0),
+                (3.0 * t - 1.0).clamp(0.0, 1.0),
+                (3.0 * t - 2.0).clamp(0.0, 1.0),
+            ],
+        };
+        Color::srgb(r, g, b)
+    }
+}
+
+/// Window/level mapping of raw intensities onto `0.0..=1.0`, as used for CT/MRI data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowLevel {
+    /// Intensity at the centre of the window.
+    pub level: f32,
+    /// Width of the intensity window.
+    pub width: f32,
+}
+
+impl WindowLevel {
+    pub fn new(level: f32, width: f32) -> Self {
+        Self { level, width }
+    }
+
+    /// Window covering the range `min..=max`.
+    pub fn from_range(min: f32, max: f32) -> Self {
+        Self {
+            level: 0.5 * (min + max),
+            width: max - min,
+        }
+    }
+
+    /// Lowest and highest intensity of the window.
+    pub fn range(&self) -> (f32, f32) {
+        (self.level - 0.5 * self.width, self.level + 0.5 * self.width)
+    }
+
+    /// Maps an intensity onto `0.0..=1.0`, clamping values outside the window.
+    pub fn normalize(&self, value: f32) -> f32 {
+        if self.width <= 0.0 {
+            return if value >= self.level { 1.0 } else { 0.0 };
+        }
+        ((value - (self.level - 0.5 * self.width)) / self.width).clamp(0.0, 1.0)
+    }
+}
+
+/// Colormap and window a plot's values were colored with, shown by `VoxelColorbar`.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelColormap {
+    pub colormap: Colormap,
+    pub window: WindowLevel,
+}
+
+impl Default for VoxelColormap {
+    fn default() -> Self {
+        Self {
+            colormap: Colormap::default(),
+            window: WindowLevel::from_range(0.0, 1.0),
+        }
+    }
+}
+
+impl VoxelColormap {
+    pub fn new(colormap: Colormap, window: WindowLevel) -> Self {
+        Self { colormap, window }
+    }
+
+    /// Color of a value, so instances can be colored consistently with the colorbar.
+    pub fn sample(&self, value: f32) -> Color {
+        self.colormap.sample(self.window.normalize(value))
+    }
+}
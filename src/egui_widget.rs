@@ -0,0 +1,221 @@
+//! Voxel plots inside egui, enabled with the `egui` feature.
+
+use crate::{VoxelPickingCursor, VoxelPlotCamera};
+use bevy::asset::RenderAssetUsages;
+use bevy::camera::{ImageRenderTarget, RenderTarget};
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy_egui::{egui, EguiTextureHandle, EguiUserTextures};
+
+/// Scrolled points per line of a `VoxelPlotCamera` zoom.
+const POINTS_PER_LINE: f32 = 50.0;
+
+/// Turns a 3D camera into the view of a `VoxelPlotWidget`.
+///
+/// The camera renders into an image that follows the size and scale factor of the widget, and
+/// the widget's pointer input is collected here so camera controllers can use it. A
+/// `VoxelPlotCamera` on the same entity consumes the input automatically.
+#[derive(Component, Clone, Debug, Default)]
+#[require(VoxelPickingCursor)]
+pub struct VoxelPlotView {
+    /// Size of the widget in logical points.
+    pub size: Vec2,
+    /// Physical pixels per logical point of the egui context.
+    pub scale_factor: f32,
+    /// Cursor position within the widget in logical points.
+    pub cursor: Option<Vec2>,
+    pub hovered: bool,
+    /// Whether a drag started on the widget and is still going on.
+    pub dragged: bool,
+    /// Drag with the primary button in logical points, since the widget was last shown.
+    pub drag_delta: Vec2,
+    /// Drag with the secondary or middle button in logical points, since the widget was last
+    /// shown.
+    pub secondary_drag_delta: Vec2,
+    /// Scrolled points while the widget is hovered, since the widget was last shown.
+    pub scroll: f32,
+    image: Option<Handle<Image>>,
+    texture: Option<egui::TextureId>,
+}
+
+impl VoxelPlotView {
+    /// Whether camera controllers should react to input, i.e. the widget is hovered or dragged.
+    pub fn input_allowed(&self) -> bool {
+        self.hovered || self.dragged
+    }
+
+    /// The image the camera renders into, once created.
+    pub fn image(&self) -> Option<&Handle<Image>> {
+        self.image.as_ref()
+    }
+
+    /// Scale factor of the render target, one until the widget was shown.
+    fn target_scale_factor(&self) -> f32 {
+        if self.scale_factor > 0.0 {
+            self.scale_factor
+        } else {
+            1.0
+        }
+    }
+
+    fn physical_size(&self) -> UVec2 {
+        (self.size * self.target_scale_factor())
+            .round()
+            .as_uvec2()
+            .max(UVec2::ONE)
+    }
+}
+
+/// Shows the image of a `VoxelPlotView` and forwards the pointer input to it.
+///
+/// ```ignore
+/// fn ui(mut contexts: EguiContexts, mut views: Query<&mut VoxelPlotView>) -> Result {
+///     let mut view = views.single_mut()?;
+///     egui::CentralPanel::default().show(contexts.ctx_mut()?, |ui| {
+///         ui.add(VoxelPlotWidget::new(&mut view));
+///     });
+///     Ok(())
+/// }
+/// ```
+pub struct VoxelPlotWidget<'a> {
+    view: &'a mut VoxelPlotView,
+    size: Option<egui::Vec2>,
+}
+
+impl<'a> VoxelPlotWidget<'a> {
+    /// Widget filling the available space.
+    pub fn new(view: &'a mut VoxelPlotView) -> Self {
+        Self { view, size: None }
+    }
+
+    /// Widget of a fixed size in logical points.
+    pub fn with_size(mut self, size: egui::Vec2) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+impl egui::Widget for VoxelPlotWidget<'_> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let size = self.size.unwrap_or_else(|| ui.available_size());
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let view = self.view;
+
+        view.size = Vec2::new(rect.width(), rect.height());
+        view.scale_factor = ui.ctx().pixels_per_point();
+        match view.texture {
+            Some(texture) => ui.painter().image(
+                texture,
+                rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                egui::Color32::WHITE,
+            ),
+            None => ui
+                .painter()
+                .rect_filled(rect, 0.0, ui.visuals().extreme_bg_color),
+        };
+
+        view.cursor = response
+            .hover_pos()
+            .map(|pos| Vec2::new(pos.x - rect.min.x, pos.y - rect.min.y));
+        view.hovered = response.hovered();
+        view.dragged = response.dragged();
+        let delta = response.drag_delta();
+        let delta = Vec2::new(delta.x, delta.y);
+        view.drag_delta = if response.dragged_by(egui::PointerButton::Primary) {
+            delta
+        } else {
+            Vec2::ZERO
+        };
+        view.secondary_drag_delta = if response.dragged_by(egui::PointerButton::Secondary)
+            || response.dragged_by(egui::PointerButton::Middle)
+        {
+            delta
+        } else {
+            Vec2::ZERO
+        };
+        view.scroll = if response.hovered() {
+            ui.input(|input| input.smooth_scroll_delta.y)
+        } else {
+            0.0
+        };
+        response
+    }
+}
+
+/// Creates and resizes the render targets of plot views and forwards their cursors to picking
+/// and their input to plot cameras.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_voxel_plot_views(
+    mut commands: Commands,
+    mut views: Query<(
+        Entity,
+        &mut VoxelPlotView,
+        &mut VoxelPickingCursor,
+        Option<&RenderTarget>,
+        Option<&mut VoxelPlotCamera>,
+    )>,
+    mut images: ResMut<Assets<Image>>,
+    mut textures: Option<ResMut<EguiUserTextures>>,
+) {
+    for (entity, mut view, mut cursor, target, controller) in &mut views {
+        cursor.set_if_neq(VoxelPickingCursor(view.cursor));
+        if let Some(mut controller) = controller {
+            controller.input.orbit += std::mem::take(&mut view.drag_delta);
+            controller.input.pan += std::mem::take(&mut view.secondary_drag_delta);
+            controller.input.zoom += std::mem::take(&mut view.scroll) / POINTS_PER_LINE;
+        }
+
+        let size = view.physical_size();
+        let extent = Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        };
+        let image = match view.image.clone() {
+            Some(image) => {
+                if let Some(image) = images.get_mut(&image) {
+                    if image.size() != size {
+                        image.resize(extent);
+                    }
+                }
+                image
+            }
+            None => {
+                let mut image = Image::new_fill(
+                    extent,
+                    TextureDimension::D2,
+                    &[0; 4],
+                    TextureFormat::Bgra8UnormSrgb,
+                    RenderAssetUsages::default(),
+                );
+                image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::RENDER_ATTACHMENT;
+                let image = images.add(image);
+                view.image = Some(image.clone());
+                image
+            }
+        };
+        if let Some(textures) = textures.as_mut() {
+            if view.texture.is_none() {
+                view.texture = Some(textures.add_image(EguiTextureHandle::Weak(image.id())));
+            }
+        }
+
+        let scale_factor = view.target_scale_factor();
+        let current = matches!(
+            target,
+            Some(RenderTarget::Image(target))
+                if target.handle == image && target.scale_factor == scale_factor
+        );
+        if !current {
+            commands
+                .entity(entity)
+                .insert(RenderTarget::Image(ImageRenderTarget {
+                    handle: image,
+                    scale_factor,
+                }));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Eye-dome lighting, a screen space pass shading point clouds by their depth.
+
+use bevy::asset::uuid_handle;
+use bevy::core_pipeline::FullscreenShader;
+use bevy::ecs::query::QueryItem;
+use bevy::image::BevyDefault;
+use bevy::prelude::*;
+use bevy::render::{
+    extract_component::{ComponentUniforms, DynamicUniformIndex, ExtractComponent},
+    render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode},
+    render_resource::{
+        binding_types::{texture_2d, texture_2d_multisampled, uniform_buffer},
+        *,
+    },
+    renderer::RenderContext,
+    view::{
+        ExtractedView, ViewDepthTexture, ViewTarget, ViewUniform, ViewUniformOffset, ViewUniforms,
+    },
+};
+
+pub(crate) const EYE_DOME_SHADER_HANDLE: Handle<Shader> =
+    uuid_handle!("3f1d8a52-7c0e-4b69-a2d4-9e6b1c57f803");
+
+/// Darkens the silhouettes and depth edges of what a 3D camera sees with eye-dome lighting,
+/// which gives unlit point clouds depth perception.
+///
+/// Voxels are depth tested and write depth in views with eye-dome lighting, so this suits
+/// opaque voxels and points. Pixels are darkened by how much farther they are than their
+/// neighbours `radius` pixels away, and background pixels next to voxels are darkened to
+/// outline silhouettes.
+#[derive(Component, ExtractComponent, ShaderType, Clone, Copy, Debug, PartialEq)]
+#[extract_component_filter(With<Camera3d>)]
+pub struct EyeDomeLighting {
+    pub strength: f32,
+    /// Distance of the sampled neighbours in pixels.
+    pub radius: f32,
+}
+
+impl Default for EyeDomeLighting {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            radius: 1.4,
+        }
+    }
+}
+
+/// Render graph node of the eye-dome lighting pass, between the main pass and post processing.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct EyeDomeLightingLabel;
+
+fn eye_dome_bind_group_layout(multisampled: bool) -> BindGroupLayoutDescriptor {
+    BindGroupLayoutDescriptor::new(
+        "eye dome lighting bind group layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::FRAGMENT,
+            (
+                uniform_buffer::<ViewUniform>(true),
+                // Depth is read as unfilterable floats, which unlike depth textures is
+                // supported by every backend.
+                if multisampled {
+                    texture_2d_multisampled(TextureSampleType::Float { filterable: false })
+                } else {
+                    texture_2d(TextureSampleType::Float { filterable: false })
+                },
+                texture_2d(TextureSampleType::Float { filterable: false }),
+                uniform_buffer::<EyeDomeLighting>(true),
+            ),
+        ),
+    )
+}
+
+#[derive(Resource)]
+pub(crate) struct EyeDomeLightingPipeline {
+    fullscreen_shader: FullscreenShader,
+}
+
+impl FromWorld for EyeDomeLightingPipeline {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            fullscreen_shader: world.resource::<FullscreenShader>().clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct EyeDomeLightingPipelineKey {
+    hdr: bool,
+    multisampled: bool,
+}
+
+impl SpecializedRenderPipeline for EyeDomeLightingPipeline {
+    type Key = EyeDomeLightingPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = Vec::new();
+        if key.multisampled {
+            shader_defs.push("MULTISAMPLED".into());
+        }
+        RenderPipelineDescriptor {
+            label: Some("eye dome lighting".into()),
+            layout: vec![eye_dome_bind_group_layout(key.multisampled)],
+            vertex: self.fullscreen_shader.to_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: EYE_DOME_SHADER_HANDLE,
+                shader_defs,
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                ..default()
+            }),
+            ..default()
+        }
+    }
+}
+
+/// Eye-dome lighting pipeline of a view.
+#[derive(Component)]
+pub(crate) struct ViewEyeDomeLightingPipeline(CachedRenderPipelineId);
+
+/// Lets the eye-dome lighting pass read the depth texture of its views.
+pub(crate) fn configure_eye_dome_lighting_views(
+    mut views: Query<&mut Camera3d, With<EyeDomeLighting>>,
+) {
+    for mut camera_3d in &mut views {
+        let mut usages = TextureUsages::from(camera_3d.depth_texture_usages);
+        usages |= TextureUsages::TEXTURE_BINDING;
+        camera_3d.depth_texture_usages = usages.into();
+    }
+}
+
+pub(crate) fn prepare_eye_dome_lighting_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<EyeDomeLightingPipeline>>,
+    pipeline: Res<EyeDomeLightingPipeline>,
+    views: Query<(Entity, &ExtractedView, &Msaa), With<EyeDomeLighting>>,
+) {
+    for (entity, view, msaa) in &views {
+        let key = EyeDomeLightingPipelineKey {
+            hdr: view.hdr,
+            multisampled: *msaa != Msaa::Off,
+        };
+        let id = pipelines.specialize(&pipeline_cache, &pipeline, key);
+        commands
+            .entity(entity)
+            .insert(ViewEyeDomeLightingPipeline(id));
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct EyeDomeLightingNode;
+
+impl ViewNode for EyeDomeLightingNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewDepthTexture,
+        &'static ViewUniformOffset,
+        &'static ViewEyeDomeLightingPipeline,
+        &'static DynamicUniformIndex<EyeDomeLighting>,
+        &'static Msaa,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (target, depth, view_offset, pipeline, settings_index, msaa): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let (Some(render_pipeline), Some(view_binding), Some(settings_binding)) = (
+            pipeline_cache.get_render_pipeline(pipeline.0),
+            world.resource::<ViewUniforms>().uniforms.binding(),
+            world
+                .resource::<ComponentUniforms<EyeDomeLighting>>()
+                .uniforms()
+                .binding(),
+        ) else {
+            return Ok(());
+        };
+
+        let post_process = target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "eye dome lighting bind group",
+            &pipeline_cache.get_bind_group_layout(&eye_dome_bind_group_layout(*msaa != Msaa::Off)),
+            &BindGroupEntries::sequential((
+                view_binding,
+                depth.view(),
+                post_process.source,
+                settings_binding,
+            )),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("eye dome lighting"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                depth_slice: None,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            ..default()
+        });
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(
+            0,
+            &bind_group,
+            &[view_offset.offset, settings_index.index()],
+        );
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
@@ -0,0 +1,795 @@
+//! Reading and writing voxel instances as PLY, PCD and CSV point clouds.
+//!
+//! All writers store position, scale and the linear RGBA color of every instance as `f32`,
+//! so files written here read back into identical `InstanceData`.
+
+use crate::nrrd::SampleType;
+use crate::InstanceData;
+use bevy::prelude::*;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Errors that can occur while reading a point cloud file.
+#[derive(Debug)]
+pub enum PointCloudError {
+    Io(std::io::Error),
+    /// The header is malformed.
+    InvalidHeader(String),
+    /// A data row or value could not be parsed.
+    InvalidData(String),
+    /// The file uses a feature that is not supported.
+    Unsupported(String),
+}
+
+impl fmt::Display for PointCloudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointCloudError::Io(err) => write!(f, "io error: {err}"),
+            PointCloudError::InvalidHeader(msg) => write!(f, "invalid header: {msg}"),
+            PointCloudError::InvalidData(msg) => write!(f, "invalid data: {msg}"),
+            PointCloudError::Unsupported(what) => write!(f, "unsupported {what}"),
+        }
+    }
+}
+
+impl std::error::Error for PointCloudError {}
+
+impl From<std::io::Error> for PointCloudError {
+    fn from(err: std::io::Error) -> Self {
+        PointCloudError::Io(err)
+    }
+}
+
+/// Encoding of the data section of PLY and PCD files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PointCloudEncoding {
+    #[default]
+    Ascii,
+    /// Little endian binary.
+    Binary,
+}
+
+const FIELDS: [&str; 8] = ["x", "y", "z", "scale", "r", "g", "b", "a"];
+
+fn instance_values(instance: &InstanceData) -> [f32; 8] {
+    let [x, y, z] = instance.position;
+    let [r, g, b, a] = instance.color;
+    [x, y, z, instance.scale, r, g, b, a]
+}
+
+fn write_values<W: Write>(
+    writer: &mut W,
+    instances: &[InstanceData],
+    encoding: PointCloudEncoding,
+    separator: &str,
+) -> std::io::Result<()> {
+    for instance in instances {
+        let values = instance_values(instance);
+        match encoding {
+            PointCloudEncoding::Ascii => {
+                let row = values.map(|v| v.to_string()).join(separator);
+                writeln!(writer, "{row}")?;
+            }
+            PointCloudEncoding::Binary => {
+                for v in values {
+                    writer.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes instances as a PLY point cloud.
+pub fn write_ply<W: Write>(
+    mut writer: W,
+    instances: &[InstanceData],
+    encoding: PointCloudEncoding,
+) -> std::io::Result<()> {
+    let format = match encoding {
+        PointCloudEncoding::Ascii => "ascii",
+        PointCloudEncoding::Binary => "binary_little_endian",
+    };
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {format} 1.0")?;
+    writeln!(
+        writer,
+        "comment written by bevy_voxel_plot, colors are linear RGBA"
+    )?;
+    writeln!(writer, "element vertex {}", instances.len())?;
+    for name in ["x", "y", "z", "scale", "red", "green", "blue", "alpha"] {
+        writeln!(writer, "property float {name}")?;
+    }
+    writeln!(writer, "end_header")?;
+    write_values(&mut writer, instances, encoding, " ")?;
+    writer.flush()
+}
+
+/// Writes instances as a PCD point cloud.
+pub fn write_pcd<W: Write>(
+    mut writer: W,
+    instances: &[InstanceData],
+    encoding: PointCloudEncoding,
+) -> std::io::Result<()> {
+    let data = match encoding {
+        PointCloudEncoding::Ascii => "ascii",
+        PointCloudEncoding::Binary => "binary",
+    };
+    writeln!(writer, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(writer, "VERSION 0.7")?;
+    writeln!(writer, "FIELDS {}", FIELDS.join(" "))?;
+    writeln!(writer, "SIZE 4 4 4 4 4 4 4 4")?;
+    writeln!(writer, "TYPE F F F F F F F F")?;
+    writeln!(writer, "COUNT 1 1 1 1 1 1 1 1")?;
+    writeln!(writer, "WIDTH {}", instances.len())?;
+    writeln!(writer, "HEIGHT 1")?;
+    writeln!(writer, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(writer, "POINTS {}", instances.len())?;
+    writeln!(writer, "DATA {data}")?;
+    write_values(&mut writer, instances, encoding, " ")?;
+    writer.flush()
+}
+
+/// Writes instances as CSV with a `x,y,z,scale,r,g,b,a` header.
+pub fn write_csv<W: Write>(mut writer: W, instances: &[InstanceData]) -> std::io::Result<()> {
+    writeln!(writer, "{}", FIELDS.join(","))?;
+    write_values(&mut writer, instances, PointCloudEncoding::Ascii, ",")?;
+    writer.flush()
+}
+
+/// Saves instances to a PLY file.
+pub fn save_ply(
+    path: impl AsRef<Path>,
+    instances: &[InstanceData],
+    encoding: PointCloudEncoding,
+) -> std::io::Result<()> {
+    write_ply(BufWriter::new(File::create(path)?), instances, encoding)
+}
+
+/// Saves instances to a PCD file.
+pub fn save_pcd(
+    path: impl AsRef<Path>,
+    instances: &[InstanceData],
+    encoding: PointCloudEncoding,
+) -> std::io::Result<()> {
+    write_pcd(BufWriter::new(File::create(path)?), instances, encoding)
+}
+
+/// Saves instances to a CSV file.
+pub fn save_csv(path: impl AsRef<Path>, instances: &[InstanceData]) -> std::io::Result<()> {
+    write_csv(BufWriter::new(File::create(path)?), instances)
+}
+
+impl SampleType {
+    fn from_ply(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => SampleType::I8,
+            "uchar" | "uint8" => SampleType::U8,
+            "short" | "int16" => SampleType::I16,
+            "ushort" | "uint16" => SampleType::U16,
+            "int" | "int32" => SampleType::I32,
+            "uint" | "uint32" => SampleType::U32,
+            "float" | "float32" => SampleType::F32,
+            "double" | "float64" => SampleType::F64,
+            _ => return None,
+        })
+    }
+
+    fn from_pcd(kind: &str, size: usize) -> Option<Self> {
+        Some(match (kind, size) {
+            ("I", 1) => SampleType::I8,
+            ("U", 1) => SampleType::U8,
+            ("I", 2) => SampleType::I16,
+            ("U", 2) => SampleType::U16,
+            ("I", 4) => SampleType::I32,
+            ("U", 4) => SampleType::U32,
+            ("I", 8) => SampleType::I64,
+            ("U", 8) => SampleType::U64,
+            ("F", 4) => SampleType::F32,
+            ("F", 8) => SampleType::F64,
+            _ => return None,
+        })
+    }
+
+    /// Value of full intensity when the type is used for color channels.
+    fn color_max(&self) -> f32 {
+        match self {
+            SampleType::I8 | SampleType::U8 => 255.0,
+            SampleType::I16 | SampleType::U16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Positions of the known fields in a row of values.
+#[derive(Default)]
+struct FieldLayout {
+    position: [Option<usize>; 3],
+    scale: Option<usize>,
+    color: [Option<usize>; 4],
+    /// Scale of integer color channels, which are read as sRGB.
+    color_max: Option<f32>,
+    /// Index of a PCL style packed `rgb`/`rgba` field.
+    packed_rgb: Option<usize>,
+}
+
+impl FieldLayout {
+    fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut layout = FieldLayout::default();
+        for (index, name) in names.into_iter().enumerate() {
+            let slot = match name.to_ascii_lowercase().as_str() {
+                "x" => &mut layout.position[0],
+                "y" => &mut layout.position[1],
+                "z" => &mut layout.position[2],
+                "scale" => &mut layout.scale,
+                "r" | "red" => &mut layout.color[0],
+                "g" | "green" => &mut layout.color[1],
+                "b" | "blue" => &mut layout.color[2],
+                "a" | "alpha" => &mut layout.color[3],
+                "rgb" | "rgba" => &mut layout.packed_rgb,
+                _ => continue,
+            };
+            *slot = Some(index);
+        }
+        layout
+    }
+
+    fn check(&self) -> Result<(), PointCloudError> {
+        if self.position.iter().any(Option::is_none) {
+            return Err(PointCloudError::InvalidHeader(
+                "missing x, y or z field".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn max_index(&self) -> usize {
+        self.position
+            .iter()
+            .chain(&[self.scale, self.packed_rgb])
+            .chain(&self.color)
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn instance(&self, values: &[f32], packed: Option<u32>) -> InstanceData {
+        let get = |index: Option<usize>, default: f32| index.map_or(default, |i| values[i]);
+        let position = self.position.map(|i| get(i, 0.0));
+        let color = if let Some(packed) = packed {
+            let [b, g, r, a] = packed.to_le_bytes();
+            let a = if self.color[3].is_some() || a == 0 {
+                255
+            } else {
+                a
+            };
+            LinearRgba::from(Color::srgba_u8(r, g, b, a)).to_f32_array()
+        } else if let Some(max) = self.color_max {
+            let [r, g, b, a] = self.color.map(|i| get(i, max) / max);
+            LinearRgba::from(Color::srgba(r, g, b, a)).to_f32_array()
+        } else {
+            self.color.map(|i| get(i, 1.0))
+        };
+        InstanceData {
+            position,
+            scale: get(self.scale, 1.0),
+            color,
+        }
+    }
+}
+
+fn parse_ascii_row(line: &str, separator: Option<char>) -> Result<Vec<f32>, PointCloudError> {
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f32>()
+            .map_err(|_| PointCloudError::InvalidData(format!("`{v}` is not a number")))
+    };
+    match separator {
+        Some(separator) => line.split(separator).map(parse).collect(),
+        None => line.split_whitespace().map(parse).collect(),
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    ty: SampleType,
+    /// Count type of list properties.
+    list: Option<SampleType>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+fn read_header_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<(), PointCloudError> {
+    line.clear();
+    if reader.read_line(line)? == 0 {
+        return Err(PointCloudError::InvalidHeader(
+            "unexpected end of file".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn read_binary_value<R: Read>(
+    reader: &mut R,
+    ty: SampleType,
+    big_endian: bool,
+) -> Result<f32, PointCloudError> {
+    let mut buf = [0u8; 8];
+    let bytes = &mut buf[..ty.size()];
+    reader.read_exact(bytes)?;
+    Ok(ty.decode(bytes, big_endian))
+}
+
+fn parse_ply_type(ty: &str) -> Result<SampleType, PointCloudError> {
+    SampleType::from_ply(ty)
+        .ok_or_else(|| PointCloudError::Unsupported(format!("property type `{ty}`")))
+}
+
+fn push_ply_property(
+    elements: &mut [PlyElement],
+    property: PlyProperty,
+    line: &str,
+) -> Result<(), PointCloudError> {
+    elements
+        .last_mut()
+        .ok_or_else(|| PointCloudError::InvalidHeader(line.to_string()))?
+        .properties
+        .push(property);
+    Ok(())
+}
+
+/// Reads the vertices of a PLY point cloud. Other elements such as faces are ignored.
+///
+/// Float colors are read as linear RGBA, integer colors as sRGB.
+pub fn read_ply<R: BufRead>(mut reader: R) -> Result<Vec<InstanceData>, PointCloudError> {
+    let mut line = String::new();
+    read_header_line(&mut reader, &mut line)?;
+    if line.trim() != "ply" {
+        return Err(PointCloudError::InvalidHeader(
+            "missing ply magic".to_string(),
+        ));
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    loop {
+        read_header_line(&mut reader, &mut line)?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["end_header"] => break,
+            ["format", format, _] => {
+                encoding = Some(match *format {
+                    "ascii" => None,
+                    "binary_little_endian" => Some(false),
+                    "binary_big_endian" => Some(true),
+                    _ => return Err(PointCloudError::Unsupported(format!("format `{format}`"))),
+                });
+            }
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| PointCloudError::InvalidHeader(line.clone()))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, ty, name] => {
+                let property = PlyProperty {
+                    name: name.to_string(),
+                    ty: parse_ply_type(ty)?,
+                    list: Some(parse_ply_type(count_ty)?),
+                };
+                push_ply_property(&mut elements, property, &line)?;
+            }
+            ["property", ty, name] => {
+                let property = PlyProperty {
+                    name: name.to_string(),
+                    ty: parse_ply_type(ty)?,
+                    list: None,
+                };
+                push_ply_property(&mut elements, property, &line)?;
+            }
+            _ => {}
+        }
+    }
+    let encoding =
+        encoding.ok_or_else(|| PointCloudError::InvalidHeader("missing format".to_string()))?;
+
+    let mut instances = Vec::new();
+    for element in &elements {
+        let is_vertex = element.name == "vertex";
+        let mut layout = FieldLayout::new(element.properties.iter().map(|p| p.name.as_str()));
+        if is_vertex {
+            layout.check()?;
+            layout.color_max = layout
+                .color
+                .iter()
+                .flatten()
+                .map(|&i| element.properties[i].ty)
+                .find(|ty| ty.color_max() > 1.0)
+                .map(|ty| ty.color_max());
+        }
+
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            match encoding {
+                None => {
+                    read_header_line(&mut reader, &mut line)?;
+                    let row = parse_ascii_row(&line, None)?;
+                    let mut row = row.into_iter();
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        let mut next = || {
+                            row.next().ok_or_else(|| {
+                                PointCloudError::InvalidData("row is too short".to_string())
+                            })
+                        };
+                        if property.list.is_some() {
+                            let count = next()? as usize;
+                            for _ in 0..count {
+                                next()?;
+                            }
+                        } else {
+                            *value = next()?;
+                        }
+                    }
+                }
+                Some(big_endian) => {
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        if let Some(count_ty) = property.list {
+                            let count = read_binary_value(&mut reader, count_ty, big_endian)?;
+                            for _ in 0..count as usize {
+                                read_binary_value(&mut reader, property.ty, big_endian)?;
+                            }
+                        } else {
+                            *value = read_binary_value(&mut reader, property.ty, big_endian)?;
+                        }
+                    }
+                }
+            }
+            if is_vertex {
+                instances.push(layout.instance(&values, None));
+            }
+        }
+        if is_vertex {
+            break;
+        }
+    }
+    Ok(instances)
+}
+
+/// Reads a PCD point cloud with ascii or binary data.
+///
+/// Float `r`, `g`, `b`, `a` fields are read as linear RGBA, integer fields and PCL style packed
+/// `rgb`/`rgba` fields as sRGB.
+pub fn read_pcd<R: BufRead>(mut reader: R) -> Result<Vec<InstanceData>, PointCloudError> {
+    let mut line = String::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut sizes: Vec<usize> = Vec::new();
+    let mut types: Vec<String> = Vec::new();
+    let mut counts: Vec<usize> = Vec::new();
+    let mut points = None;
+    let binary = loop {
+        read_header_line(&mut reader, &mut line)?;
+        let mut tokens = line.split_whitespace();
+        let Some(key) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+        let parse_usize = |v: &&str| {
+            v.parse::<usize>()
+                .map_err(|_| PointCloudError::InvalidHeader(line.clone()))
+        };
+        match key {
+            "FIELDS" => fields = rest.iter().map(|v| v.to_string()).collect(),
+            "SIZE" => sizes = rest.iter().map(parse_usize).collect::<Result<_, _>>()?,
+            "TYPE" => types = rest.iter().map(|v| v.to_string()).collect(),
+            "COUNT" => counts = rest.iter().map(parse_usize).collect::<Result<_, _>>()?,
+            "POINTS" => points = rest.first().map(parse_usize).transpose()?,
+            "DATA" => match rest.first() {
+                Some(&"ascii") => break false,
+                Some(&"binary") => break true,
+                other => {
+                    return Err(PointCloudError::Unsupported(format!(
+                        "data encoding `{}`",
+                        other.unwrap_or(&"")
+                    )))
+                }
+            },
+            _ => {}
+        }
+    };
+
+    if counts.is_empty() {
+        counts = vec![1; fields.len()];
+    }
+    if sizes.len() != fields.len() || types.len() != fields.len() || counts.len() != fields.len() {
+        return Err(PointCloudError::InvalidHeader(
+            "FIELDS, SIZE, TYPE and COUNT differ in length".to_string(),
+        ));
+    }
+    // Read the data first, so that header counts can be checked against its length.
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    // Every value takes at least a byte, in binary as well as in ascii.
+    let columns = counts
+        .iter()
+        .try_fold(0usize, |columns, &count| columns.checked_add(count));
+    if columns.is_none_or(|columns| columns > fields.len() + bytes.len()) {
+        return Err(PointCloudError::InvalidData(format!(
+            "COUNT {counts:?} exceeds the {} bytes of data",
+            bytes.len()
+        )));
+    }
+
+    let sample_types = types
+        .iter()
+        .zip(&sizes)
+        .map(|(ty, &size)| {
+            SampleType::from_pcd(ty, size)
+                .ok_or_else(|| PointCloudError::Unsupported(format!("field type {ty}{size}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every field contributes `count` values to a row, the layout refers to the first of them.
+    let mut columns = Vec::new();
+    for (field, &count) in fields.iter().zip(&counts) {
+        columns.push(field.as_str());
+        columns.extend(std::iter::repeat_n("_", count.saturating_sub(1)));
+    }
+    let column_types: Vec<SampleType> = sample_types
+        .iter()
+        .zip(&counts)
+        .flat_map(|(&ty, &count)| std::iter::repeat_n(ty, count))
+        .collect();
+    let mut layout = FieldLayout::new(columns);
+    layout.check()?;
+    layout.color_max = layout
+        .color
+        .iter()
+        .flatten()
+        .map(|&i| column_types[i])
+        .find(|ty| ty.color_max() > 1.0)
+        .map(|ty| ty.color_max());
+    let packed_ty = layout.packed_rgb.map(|i| column_types[i]);
+
+    let mut instances = Vec::new();
+    if binary {
+        let stride: usize = column_types.iter().map(SampleType::size).sum();
+        let rows = points.unwrap_or(bytes.len() / stride.max(1));
+        let needed = rows.checked_mul(stride).ok_or_else(|| {
+            PointCloudError::InvalidData(format!("{rows} points of {stride} bytes"))
+        })?;
+        if bytes.len() < needed {
+            return Err(PointCloudError::InvalidData(format!(
+                "expected {rows} points, found {}",
+                bytes.len() / stride.max(1)
+            )));
+        }
+        let mut values = vec![0.0; column_types.len()];
+        for row in bytes.chunks_exact(stride).take(rows) {
+            let mut offset = 0;
+            let mut packed = None;
+            for (column, (value, ty)) in values.iter_mut().zip(&column_types).enumerate() {
+                let sample = &row[offset..offset + ty.size()];
+                if Some(column) == layout.packed_rgb && ty.size() == 4 {
+                    packed = Some(u32::from_le_bytes(sample.try_into().unwrap()));
+                }
+                *value = ty.decode(sample, false);
+                offset += ty.size();
+            }
+            instances.push(layout.instance(&values, packed));
+        }
+    } else {
+        let text = String::from_utf8(bytes)
+            .map_err(|_| PointCloudError::InvalidData("data is not UTF-8".to_string()))?;
+        for row in text.lines().filter(|l| !l.trim().is_empty()) {
+            let values = parse_ascii_row(row, None)?;
+            if values.len() < column_types.len() {
+                return Err(PointCloudError::InvalidData(format!(
+                    "row `{row}` is too short"
+                )));
+            }
+            let packed = layout.packed_rgb.map(|i| match packed_ty {
+                Some(SampleType::F32) => values[i].to_bits(),
+                _ => row.split_whitespace().nth(i).unwrap().parse().unwrap_or(0),
+            });
+            instances.push(layout.instance(&values, packed));
+        }
+    }
+    Ok(instances)
+}
+
+/// Reads instances from CSV. A header naming the `x`, `y`, `z`, `scale`, `r`, `g`, `b`, `a`
+/// columns is optional, without it the columns are expected in that order.
+pub fn read_csv<R: BufRead>(reader: R) -> Result<Vec<InstanceData>, PointCloudError> {
+    let mut instances = Vec::new();
+    let mut layout: Option<FieldLayout> = None;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = match parse_ascii_row(&line, Some(',')) {
+            Ok(values) => values,
+            Err(_) if layout.is_none() => {
+                layout = Some(FieldLayout::new(line.split(',').map(str::trim)));
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let layout =
+            layout.get_or_insert_with(|| FieldLayout::new(FIELDS.into_iter().take(values.len())));
+        layout.check()?;
+        if layout.max_index() >= values.len() {
+            return Err(PointCloudError::InvalidData(format!(
+                "row `{line}` is too short"
+            )));
+        }
+        instances.push(layout.instance(&values, None));
+    }
+    Ok(instances)
+}
+
+/// Loads instances from a PLY file.
+pub fn load_ply(path: impl AsRef<Path>) -> Result<Vec<InstanceData>, PointCloudError> {
+    read_ply(BufReader::new(File::open(path)?))
+}
+
+/// Loads instances from a PCD file.
+pub fn load_pcd(path: impl AsRef<Path>) -> Result<Vec<InstanceData>, PointCloudError> {
+    read_pcd(BufReader::new(File::open(path)?))
+}
+
+/// Loads instances from a CSV file.
+pub fn load_csv(path: impl AsRef<Path>) -> Result<Vec<InstanceData>, PointCloudError> {
+    read_csv(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Largest color error of channels stored as `u8`.
+    const U8_TOLERANCE: f32 = 1.0 / 255.0;
+
+    fn instances() -> Vec<InstanceData> {
+        vec![
+            InstanceData {
+                position: [0.0, -1.5, 2.25],
+                scale: 1.0,
+                color: [1.0, 0.0, 0.5, 1.0],
+            },
+            InstanceData {
+                position: [1e-3, 12345.678, -0.1],
+                scale: 0.25,
+                color: [0.2, 0.4, 0.6, 0.8],
+            },
+            InstanceData {
+                position: [-7.0, 0.5, 3.0],
+                scale: 3.5,
+                color: [0.0, 0.0, 0.0, 0.0],
+            },
+        ]
+    }
+
+    fn assert_same(read: &[InstanceData], written: &[InstanceData], color_tolerance: f32) {
+        assert_eq!(read.len(), written.len());
+        for (read, written) in read.iter().zip(written) {
+            assert_eq!(read.position, written.position);
+            assert_eq!(read.scale, written.scale);
+            for (a, b) in read.color.iter().zip(&written.color) {
+                assert!((a - b).abs() <= color_tolerance, "{a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn ply_round_trip() {
+        for encoding in [PointCloudEncoding::Ascii, PointCloudEncoding::Binary] {
+            let mut bytes = Vec::new();
+            write_ply(&mut bytes, &instances(), encoding).unwrap();
+            assert_same(&read_ply(bytes.as_slice()).unwrap(), &instances(), 0.0);
+        }
+    }
+
+    #[test]
+    fn pcd_round_trip() {
+        for encoding in [PointCloudEncoding::Ascii, PointCloudEncoding::Binary] {
+            let mut bytes = Vec::new();
+            write_pcd(&mut bytes, &instances(), encoding).unwrap();
+            assert_same(&read_pcd(bytes.as_slice()).unwrap(), &instances(), 0.0);
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut bytes = Vec::new();
+        write_csv(&mut bytes, &instances()).unwrap();
+        assert_same(&read_csv(bytes.as_slice()).unwrap(), &instances(), 0.0);
+    }
+
+    #[test]
+    fn ply_u8_colors() {
+        let file = "ply\nformat binary_little_endian 1.0\nelement vertex 2\n\
+            property float x\nproperty float y\nproperty float z\nproperty uchar red\n\
+            property uchar green\nproperty uchar blue\nproperty uchar alpha\nend_header\n";
+        let mut bytes = file.as_bytes().to_vec();
+        for (position, color) in [
+            ([0.0f32, -1.5, 2.25], [255u8, 0, 128, 255]),
+            ([1.0, 2.0, 3.0], [77, 179, 51, 204]),
+        ] {
+            for v in position {
+                bytes.extend(v.to_le_bytes());
+            }
+            bytes.extend(color);
+        }
+        let read = read_ply(bytes.as_slice()).unwrap();
+        // Integer colors are sRGB, compare them before the conversion to linear.
+        let read: Vec<InstanceData> = read
+            .into_iter()
+            .map(|instance| InstanceData {
+                color: Srgba::from(LinearRgba::from_f32_array(instance.color)).to_f32_array(),
+                ..instance
+            })
+            .collect();
+        let expected = [
+            InstanceData {
+                position: [0.0, -1.5, 2.25],
+                scale: 1.0,
+                color: [1.0, 0.0, 0.5, 1.0],
+            },
+            InstanceData {
+                position: [1.0, 2.0, 3.0],
+                scale: 1.0,
+                color: [0.3, 0.7, 0.2, 0.8],
+            },
+        ];
+        assert_same(&read, &expected, U8_TOLERANCE);
+    }
+
+    #[test]
+    fn malformed_header() {
+        let missing_magic = "format ascii 1.0\nelement vertex 0\nend_header\n";
+        assert!(matches!(
+            read_ply(missing_magic.as_bytes()),
+            Err(PointCloudError::InvalidHeader(_))
+        ));
+        let missing_z = "FIELDS x y\nSIZE 4 4\nTYPE F F\nPOINTS 0\nDATA ascii\n";
+        assert!(matches!(
+            read_pcd(missing_z.as_bytes()),
+            Err(PointCloudError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn hostile_counts() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 18446744073709551615\n\
+            property float x\nproperty float y\nproperty float z\nend_header\n0 0 0\n";
+        assert!(read_ply(ply.as_bytes()).is_err());
+
+        let pcd = |points: &str, count: &str| {
+            format!(
+                "FIELDS x y z\nSIZE 4 4 4\nTYPE F F F\nCOUNT {count}\nPOINTS {points}\n\
+                 DATA binary\n0123456789ab"
+            )
+        };
+        for (points, count) in [
+            ("18446744073709551615", "1 1 1"),
+            ("1000000", "1 1 1"),
+            ("1", "1 1 4000000000"),
+            ("1", "1 1 18446744073709551615"),
+        ] {
+            assert!(matches!(
+                read_pcd(pcd(points, count).as_bytes()),
+                Err(PointCloudError::InvalidData(_))
+            ));
+        }
+        assert_eq!(read_pcd(pcd("1", "1 1 1").as_bytes()).unwrap().len(), 1);
+    }
+}
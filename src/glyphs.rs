@@ -0,0 +1,196 @@
+//! Several glyph meshes within one plot, chosen per instance.
+
+use crate::{InstanceMaterialData, ReducedInstances};
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy::render::{sync_world::RenderEntity, Extract};
+use std::ops::Range;
+
+/// Default number of instances sorted together before they are grouped by glyph.
+const DEFAULT_GLYPH_BATCH: usize = 4096;
+
+/// Draws the instances of a plot with one of several glyph meshes, e.g. cubes, spheres and
+/// octahedra, chosen by a per-instance glyph id.
+///
+/// Instance `i` is drawn with `meshes[ids[i]]`. Instances without an id or with an id outside of
+/// `meshes`, as well as aggregated level of detail nodes, use the plot's `Mesh3d`. Glyph meshes
+/// need the same vertex attributes and topology as the plot's mesh, which holds for Bevy's
+/// built-in primitives. Instances of other glyph meshes are drawn with the plot's mesh and a
+/// warning is logged.
+///
+/// Instances are sorted back to front in batches of `batch` instances, within which they are
+/// drawn glyph by glyph. The transparency order is exact between batches, smaller batches
+/// order more exactly at the cost of more draws.
+#[derive(Component, Clone, Debug)]
+pub struct VoxelGlyphs {
+    pub meshes: Vec<Handle<Mesh>>,
+    /// Glyph id of every instance, in the order of `InstanceMaterialData::instances`.
+    pub ids: Vec<u8>,
+    pub batch: usize,
+}
+
+impl VoxelGlyphs {
+    pub fn new(meshes: Vec<Handle<Mesh>>, ids: Vec<u8>) -> Self {
+        Self {
+            meshes,
+            ids,
+            batch: DEFAULT_GLYPH_BATCH,
+        }
+    }
+
+    pub fn with_batch(mut self, batch: usize) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Mesh of the instance at `index`, `None` for the plot's mesh. `usable` tells which glyph
+    /// meshes can be drawn in place of the plot's mesh.
+    fn mesh(&self, index: usize, usable: &[bool]) -> Option<AssetId<Mesh>> {
+        let id = *self.ids.get(index)? as usize;
+        if !usable.get(id).copied().unwrap_or(false) {
+            return None;
+        }
+        self.meshes.get(id).map(Handle::id)
+    }
+}
+
+/// Consecutive instances of a buffer drawn with one mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct GlyphDraw {
+    /// Mesh of the draw, `None` for the plot's mesh.
+    pub(crate) mesh: Option<AssetId<Mesh>>,
+    pub(crate) instances: Range<u32>,
+}
+
+impl GlyphDraw {
+    /// Draw of the first `len` instances with the plot's mesh.
+    pub(crate) fn plot_mesh(len: usize) -> Vec<GlyphDraw> {
+        vec![GlyphDraw {
+            mesh: None,
+            instances: 0..len as u32,
+        }]
+    }
+}
+
+/// Glyph meshes of the extracted instances of a plot.
+#[derive(Component)]
+pub(crate) struct InstanceGlyphs {
+    /// Mesh of every extracted instance, `None` for the plot's mesh.
+    meshes: Vec<Option<AssetId<Mesh>>>,
+    batch: usize,
+    /// Whether some meshes were not loaded yet, so that their layouts are still to be checked.
+    pending: bool,
+}
+
+impl InstanceGlyphs {
+    /// Groups instances sorted back to front by glyph within batches, keeping the depth order
+    /// within each glyph, and returns the resulting draws. Keys are indices into the extracted
+    /// instances.
+    pub(crate) fn arrange<K>(&self, sorted: &mut [(K, u32)]) -> Vec<GlyphDraw> {
+        let mesh = |index: u32| self.meshes.get(index as usize).copied().flatten();
+        let mut draws: Vec<GlyphDraw> = Vec::new();
+        let mut start = 0;
+        for batch in sorted.chunks_mut(self.batch.max(1)) {
+            batch.sort_by_key(|&(_, index)| mesh(index));
+            for &(_, index) in batch.iter() {
+                let mesh = mesh(index);
+                match draws.last_mut() {
+                    Some(draw) if draw.mesh == mesh => draw.instances.end += 1,
+                    _ => draws.push(GlyphDraw {
+                        mesh,
+                        instances: start..start + 1,
+                    }),
+                }
+                start += 1;
+            }
+        }
+        draws
+    }
+}
+
+/// Whether a glyph mesh can be drawn with the pipeline of the plot's mesh.
+fn is_compatible(plot: &Mesh, glyph: &Mesh) -> bool {
+    let attributes =
+        |mesh: &Mesh| -> Vec<_> { mesh.attributes().map(|(a, _)| (a.id, a.format)).collect() };
+    plot.primitive_topology() == glyph.primitive_topology() && attributes(plot) == attributes(glyph)
+}
+
+/// Extracts the glyph meshes of the drawn instances, replacing glyph meshes that cannot be drawn
+/// with the plot's pipeline by the plot's mesh.
+#[allow(clippy::type_complexity)]
+pub(crate) fn extract_instance_glyphs(
+    mut commands: Commands,
+    plots: Extract<
+        Query<(
+            Entity,
+            RenderEntity,
+            Ref<InstanceMaterialData>,
+            Option<Ref<ReducedInstances>>,
+            Option<Ref<VoxelGlyphs>>,
+            Ref<Mesh3d>,
+        )>,
+    >,
+    meshes: Extract<Res<Assets<Mesh>>>,
+    extracted: Query<&InstanceGlyphs>,
+    mut warned: Local<HashSet<(Entity, AssetId<Mesh>)>>,
+) {
+    for (main_entity, entity, instance_data, reduced, glyphs, mesh) in &plots {
+        let Some(glyphs) = glyphs else {
+            if extracted.contains(entity) {
+                commands.entity(entity).remove::<InstanceGlyphs>();
+            }
+            continue;
+        };
+        let changed = instance_data.is_changed()
+            || glyphs.is_changed()
+            || mesh.is_changed()
+            || reduced.as_ref().is_some_and(|reduced| reduced.is_changed())
+            || extracted
+                .get(entity)
+                .ok()
+                .is_none_or(|glyphs| glyphs.pending);
+        if !changed {
+            continue;
+        }
+
+        let plot_mesh = meshes.get(&mesh.0);
+        let mut pending = false;
+        let usable: Vec<bool> = glyphs
+            .meshes
+            .iter()
+            .map(|handle| match (plot_mesh, meshes.get(handle)) {
+                (Some(plot_mesh), Some(glyph_mesh)) => {
+                    let compatible = is_compatible(plot_mesh, glyph_mesh);
+                    if !compatible && warned.insert((main_entity, handle.id())) {
+                        warn!(
+                            "Glyph mesh {:?} of plot {main_entity} differs from the plot's mesh in \
+                             vertex attributes or topology, drawing its instances with the \
+                             plot's mesh",
+                            handle.id()
+                        );
+                    }
+                    compatible
+                }
+                _ => {
+                    pending = true;
+                    true
+                }
+            })
+            .collect();
+        let meshes = match reduced {
+            Some(reduced) => reduced
+                .indices
+                .iter()
+                .map(|&i| glyphs.mesh(i as usize, &usable))
+                .collect(),
+            None => (0..instance_data.instances.len())
+                .map(|i| glyphs.mesh(i, &usable))
+                .collect(),
+        };
+        commands.entity(entity).insert(InstanceGlyphs {
+            meshes,
+            batch: glyphs.batch,
+            pending,
+        });
+    }
+}
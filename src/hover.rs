@@ -0,0 +1,74 @@
+//! Highlighting of the voxel under the cursor.
+
+use crate::picking::{cast_voxel_ray, CursorRay, PickablePlot};
+use crate::VoxelPickable;
+use bevy::prelude::*;
+
+/// Highlights the voxel under the cursor and reports it in the `VoxelHover` resource.
+///
+/// The highlight is applied in the shader, the instance data is left untouched.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+#[require(VoxelPickable)]
+pub struct VoxelHoverHighlight {
+    /// Color the hovered voxel is drawn with.
+    pub color: Color,
+}
+
+impl Default for VoxelHoverHighlight {
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(1.0, 1.0, 0.0),
+        }
+    }
+}
+
+/// Scalar values of the instances of a plot, e.g. the intensities they were colored by, which are
+/// reported on hover.
+#[derive(Component, Clone, Debug, Default)]
+pub struct VoxelValues(pub Vec<f32>);
+
+/// A voxel under the cursor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HoveredVoxel {
+    /// The plot entity.
+    pub entity: Entity,
+    /// Index of the instance in `InstanceMaterialData::instances`.
+    pub index: usize,
+    pub position: Vec3,
+    pub scale: f32,
+    pub color: Color,
+    /// Value from the plot's `VoxelValues`, if present.
+    pub value: Option<f32>,
+    /// Point where the cursor ray enters the voxel, in world space.
+    pub point: Vec3,
+}
+
+/// The voxel under the cursor on plots with `VoxelHoverHighlight`, e.g. for showing a tooltip.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelHover(pub Option<HoveredVoxel>);
+
+pub(crate) fn update_voxel_hover(
+    cursor_ray: CursorRay,
+    plots: Query<PickablePlot, With<VoxelHoverHighlight>>,
+    values: Query<&VoxelValues>,
+    mut hover: ResMut<VoxelHover>,
+) {
+    let hovered = cursor_ray.get().and_then(|(_, _, ray)| {
+        let hit = cast_voxel_ray(&plots, ray)?;
+        let instance = plots.get(hit.entity).ok()?.1.instances[hit.index];
+        let [r, g, b, a] = instance.color;
+        Some(HoveredVoxel {
+            entity: hit.entity,
+            index: hit.index,
+            position: Vec3::from_array(instance.position),
+            scale: instance.scale,
+            color: Color::linear_rgba(r, g, b, a),
+            value: values
+                .get(hit.entity)
+                .ok()
+                .and_then(|values| values.0.get(hit.index).copied()),
+            point: ray.get_point(hit.distance),
+        })
+    });
+    hover.set_if_neq(VoxelHover(hovered));
+}
@@ -1,3 +1,56 @@
+mod axes;
 mod bevy_voxel_plot;
+mod bounds;
+mod budget;
+mod cache;
+mod camera;
+mod chunks;
+mod clipping;
+mod colorbar;
+mod colormap;
+#[cfg(feature = "egui")]
+mod egui_widget;
+mod eye_dome;
+mod formats;
+mod glyphs;
+mod hover;
+mod lod;
+mod nrrd;
+mod occlusion;
+mod offscreen;
+mod picking;
+mod pixel_size;
+mod selection;
+mod sequence;
+mod slices;
+mod sprites;
+mod stream;
+mod time_series;
+mod uniforms;
 
+pub use axes::VoxelAxes;
 pub use bevy_voxel_plot::*;
+pub use budget::{Decimation, VoxelBudget, VoxelBudgetReport};
+pub use cache::*;
+pub use camera::{ViewPreset, VoxelPlotCamera};
+pub use clipping::{ClipPlane, VoxelClip, VoxelSlab, MAX_CLIP_PLANES};
+pub use colorbar::VoxelColorbar;
+pub use colormap::*;
+#[cfg(feature = "egui")]
+pub use egui_widget::{VoxelPlotView, VoxelPlotWidget};
+pub use eye_dome::EyeDomeLighting;
+pub use formats::*;
+pub use glyphs::VoxelGlyphs;
+pub use hover::{HoveredVoxel, VoxelHover, VoxelHoverHighlight, VoxelValues};
+pub use lod::VoxelLod;
+pub use nrrd::*;
+pub use occlusion::VoxelAmbientOcclusion;
+pub use offscreen::{headless_plugins, OffscreenFrame, OffscreenRender};
+pub use picking::{VoxelClick, VoxelPickable, VoxelPickingCursor};
+pub use pixel_size::VoxelPixelSize;
+pub use selection::VoxelSelection;
+pub use sequence::{CameraKeyframe, CameraPath, FrameSequence};
+pub use slices::{SliceImages, SlicePlane, VoxelSlices};
+pub use sprites::{SpriteShape, SpriteSize, VoxelSprites};
+pub use stream::InstanceStream;
+pub use time_series::{VoxelFrame, VoxelTimeSeries};
@@ -0,0 +1,407 @@
+//! Octree level of detail for large plots.
+//!
+//! Every octree node stores an aggregated voxel standing in for all instances below it. Each
+//! frame the tree is refined from the root, largest projected nodes first, until the instance
+//! budget is exhausted or the remaining nodes are smaller than the detail threshold.
+
+use crate::{InstanceData, InstanceMaterialData, ReducedInstances};
+use bevy::camera::primitives::MeshAabb;
+use bevy::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Nodes with at most this many instances are not split further.
+const LEAF_INSTANCES: usize = 8;
+/// Maximum depth of the octree, guarding against many instances at the same position.
+const MAX_DEPTH: u32 = 21;
+
+/// Enables octree level of detail on an entity with `InstanceMaterialData`.
+///
+/// Distant parts of the plot are drawn as aggregated voxels with averaged color and accumulated
+/// opacity, near parts in full detail. The detail is chosen for the active 3D camera every frame.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelLod {
+    /// Maximum number of instances drawn.
+    pub budget: usize,
+    /// Nodes whose projected size is below this many pixels are drawn as a single voxel.
+    pub detail_pixels: f32,
+}
+
+impl Default for VoxelLod {
+    fn default() -> Self {
+        Self {
+            budget: 1_000_000,
+            detail_pixels: 2.0,
+        }
+    }
+}
+
+impl VoxelLod {
+    pub fn with_budget(budget: usize) -> Self {
+        Self {
+            budget,
+            ..default()
+        }
+    }
+}
+
+struct OctreeNode {
+    center: Vec3,
+    edge: f32,
+    /// Aggregated voxel, with a volume preserving scale that is not yet clamped to the node.
+    aggregate: InstanceData,
+    children: Vec<u32>,
+    /// Range of the node's instances in `VoxelOctree::order`.
+    start: u32,
+    end: u32,
+}
+
+/// Octree over the instances of a plot.
+pub(crate) struct VoxelOctree {
+    nodes: Vec<OctreeNode>,
+    order: Vec<u32>,
+}
+
+impl VoxelOctree {
+    pub(crate) fn new(instances: &[InstanceData]) -> Self {
+        let (min, max) = instances.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), instance| {
+                let position = Vec3::from_array(instance.position);
+                (min.min(position), max.max(position))
+            },
+        );
+        let mut octree = VoxelOctree {
+            nodes: Vec::new(),
+            order: (0..instances.len() as u32).collect(),
+        };
+        if !instances.is_empty() {
+            let edge = (max - min).max_element().max(f32::EPSILON);
+            octree.build(instances, 0, instances.len(), (min + max) * 0.5, edge, 0);
+        }
+        octree
+    }
+
+    fn build(
+        &mut self,
+        instances: &[InstanceData],
+        start: usize,
+        end: usize,
+        center: Vec3,
+        edge: f32,
+        depth: u32,
+    ) -> u32 {
+        let node = self.nodes.len() as u32;
+        self.nodes.push(OctreeNode {
+            center,
+            edge,
+            aggregate: aggregate(
+                self.order[start..end]
+                    .iter()
+                    .map(|&i| &instances[i as usize]),
+            ),
+            children: Vec::new(),
+            start: start as u32,
+            end: end as u32,
+        });
+        if end - start <= LEAF_INSTANCES || depth == MAX_DEPTH {
+            return node;
+        }
+
+        let octant = |i: &u32| {
+            let position = Vec3::from_array(instances[*i as usize].position);
+            (position.x >= center.x) as usize
+                | ((position.y >= center.y) as usize) << 1
+                | ((position.z >= center.z) as usize) << 2
+        };
+        self.order[start..end].sort_unstable_by_key(octant);
+
+        let mut children = Vec::new();
+        let mut first = start;
+        while first < end {
+            let index = octant(&self.order[first]);
+            let last = first + self.order[first..end].partition_point(|i| octant(i) == index);
+            let offset = Vec3::new(
+                if index & 1 != 0 { 0.25 } else { -0.25 },
+                if index & 2 != 0 { 0.25 } else { -0.25 },
+                if index & 4 != 0 { 0.25 } else { -0.25 },
+            ) * edge;
+            children.push(self.build(
+                instances,
+                first,
+                last,
+                center + offset,
+                edge * 0.5,
+                depth + 1,
+            ));
+            first = last;
+        }
+        self.nodes[node as usize].children = children;
+        node
+    }
+
+    /// Selects the nodes to draw, returning `(node, expanded)` pairs where expanded leaves are
+    /// drawn with their original instances.
+    fn select(&self, view: &LodView, budget: usize, detail_pixels: f32) -> Vec<(u32, bool)> {
+        let mut selection = Vec::new();
+        if self.nodes.is_empty() {
+            return selection;
+        }
+        let mut count = 1;
+        let mut queue = BinaryHeap::from([Candidate {
+            pixels: view.projected_size(&self.nodes[0]),
+            node: 0,
+        }]);
+        while let Some(Candidate { pixels, node }) = queue.pop() {
+            let octree_node = &self.nodes[node as usize];
+            let cost = if octree_node.children.is_empty() {
+                (octree_node.end - octree_node.start) as usize
+            } else {
+                octree_node.children.len()
+            } - 1;
+            if pixels < detail_pixels || count + cost > budget {
+                selection.push((node, false));
+                continue;
+            }
+            count += cost;
+            if octree_node.children.is_empty() {
+                selection.push((node, true));
+            } else {
+                queue.extend(octree_node.children.iter().map(|&child| Candidate {
+                    pixels: view.projected_size(&self.nodes[child as usize]),
+                    node: child,
+                }));
+            }
+        }
+        selection
+    }
+
+    /// Instances drawn for a selection, with aggregated voxels no larger than their node.
+    fn instances(
+        &self,
+        instances: &[InstanceData],
+        selection: &[(u32, bool)],
+        mesh_edge: f32,
+    ) -> ReducedInstances {
+        let mut drawn = ReducedInstances {
+            instances: Vec::new(),
+            indices: Vec::new(),
+        };
+        for &(node, expanded) in selection {
+            let node = &self.nodes[node as usize];
+            if expanded || node.end - node.start == 1 {
+                let order = &self.order[node.start as usize..node.end as usize];
+                drawn
+                    .instances
+                    .extend(order.iter().map(|&i| instances[i as usize]));
+                drawn.indices.extend_from_slice(order);
+            } else {
+                let mut aggregate = node.aggregate;
+                aggregate.scale = aggregate.scale.min(node.edge / mesh_edge);
+                drawn.instances.push(aggregate);
+                drawn.indices.push(u32::MAX);
+            }
+        }
+        drawn
+    }
+}
+
+/// Averages the color weighted by opacity and accumulates the opacity of `n` instances as if a
+/// ray crossed `n^(1/3)` of them. The scale preserves the total volume of the instances.
+fn aggregate<'a>(instances: impl Iterator<Item = &'a InstanceData>) -> InstanceData {
+    let mut count = 0.0;
+    let mut position = Vec3::ZERO;
+    let mut color = Vec3::ZERO;
+    let mut alpha = 0.0;
+    let mut volume = 0.0;
+    for instance in instances {
+        let [r, g, b, a] = instance.color;
+        count += 1.0;
+        position += Vec3::from_array(instance.position);
+        color += Vec3::new(r, g, b) * a;
+        alpha += a;
+        volume += instance.scale.powi(3);
+    }
+    let color = if alpha > 0.0 { color / alpha } else { color };
+    let mean_alpha = (alpha / count).clamp(0.0, 1.0);
+    InstanceData {
+        position: (position / count).to_array(),
+        scale: f32::cbrt(volume),
+        color: [
+            color.x,
+            color.y,
+            color.z,
+            1.0 - (1.0 - mean_alpha).powf(f32::cbrt(count)),
+        ],
+    }
+}
+
+struct Candidate {
+    pixels: f32,
+    node: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.pixels.total_cmp(&other.pixels)
+    }
+}
+
+/// Camera parameters needed to estimate the on-screen size of objects.
+pub(crate) struct LodView {
+    position: Vec3,
+    forward: Vec3,
+    /// Pixels per world unit at unit depth for perspective, or at any depth for orthographic.
+    pixels_per_unit: f32,
+    orthographic: bool,
+}
+
+impl LodView {
+    /// Uses the first active 3D camera.
+    pub(crate) fn from_cameras<'a>(
+        cameras: impl IntoIterator<Item = (&'a Camera, &'a GlobalTransform, &'a Projection)>,
+    ) -> Option<Self> {
+        let (camera, transform, projection) =
+            cameras.into_iter().find(|(camera, ..)| camera.is_active)?;
+        let height = camera.physical_viewport_size()?.y as f32;
+        let (pixels_per_unit, orthographic) = match projection {
+            Projection::Perspective(perspective) => {
+                (height / (2.0 * (perspective.fov * 0.5).tan()), false)
+            }
+            Projection::Orthographic(orthographic) => {
+                (height / orthographic.area.height().max(f32::EPSILON), true)
+            }
+            Projection::Custom(_) => (height / (2.0 * (std::f32::consts::FRAC_PI_8).tan()), false),
+        };
+        Some(Self {
+            position: transform.translation(),
+            forward: transform.forward().as_vec3(),
+            pixels_per_unit,
+            orthographic,
+        })
+    }
+
+    /// Whether an object lies entirely behind a perspective camera.
+    pub(crate) fn is_behind(&self, center: Vec3, radius: f32) -> bool {
+        !self.orthographic && (center - self.position).dot(self.forward) + radius <= 0.0
+    }
+
+    /// Projected size in pixels of an object with the given extent, `radius` being the distance
+    /// from `center` to its farthest point. Objects entirely behind a perspective camera have a
+    /// size of zero.
+    pub(crate) fn pixels(&self, center: Vec3, extent: f32, radius: f32) -> f32 {
+        if self.orthographic {
+            return extent * self.pixels_per_unit;
+        }
+        if self.is_behind(center, radius) {
+            return 0.0;
+        }
+        let depth = (center - self.position).dot(self.forward) - radius;
+        // Objects straddling the camera plane are as large as they can be.
+        extent * self.pixels_per_unit / depth.max(f32::EPSILON)
+    }
+
+    fn projected_size(&self, node: &OctreeNode) -> f32 {
+        self.pixels(node.center, node.edge, node.edge * 0.5 * 3f32.sqrt())
+    }
+}
+
+/// Largest edge of a plot's mesh, i.e. the size of a voxel with scale 1.
+pub(crate) fn mesh_edge(meshes: &Assets<Mesh>, mesh: &Mesh3d) -> f32 {
+    loaded_mesh_edge(meshes, mesh).unwrap_or(1.0)
+}
+
+/// Largest edge of a plot's mesh, `None` while the mesh is not loaded.
+pub(crate) fn loaded_mesh_edge(meshes: &Assets<Mesh>, mesh: &Mesh3d) -> Option<f32> {
+    meshes
+        .get(mesh)
+        .and_then(|mesh| mesh.compute_aabb())
+        .map(|aabb| 2.0 * aabb.half_extents.max_element())
+}
+
+/// Octree of a plot together with the current selection.
+#[derive(Component)]
+pub(crate) struct VoxelLodState {
+    octree: VoxelOctree,
+    selection: Vec<(u32, bool)>,
+    mesh_edge: f32,
+}
+
+/// Rebuilds the octree when the data changes and selects the drawn nodes for the active camera.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_voxel_lod(
+    mut commands: Commands,
+    mut plots: Query<(
+        Entity,
+        Ref<InstanceMaterialData>,
+        Ref<VoxelLod>,
+        &Mesh3d,
+        Option<&mut VoxelLodState>,
+    )>,
+    meshes: Res<Assets<Mesh>>,
+    cameras: Query<(&Camera, &GlobalTransform, &Projection), With<Camera3d>>,
+    mut removed: RemovedComponents<VoxelLod>,
+    mut data: Query<&mut InstanceMaterialData, Without<VoxelLod>>,
+) {
+    for entity in removed.read() {
+        if let Ok(mut instance_data) = data.get_mut(entity) {
+            commands
+                .entity(entity)
+                .remove::<(ReducedInstances, VoxelLodState)>();
+            instance_data.set_changed();
+        }
+    }
+
+    let Some(view) = LodView::from_cameras(cameras.iter()) else {
+        return;
+    };
+
+    for (entity, instance_data, lod, mesh, state) in &mut plots {
+        let mesh_edge = mesh_edge(&meshes, mesh);
+        let mut rebuilt = None;
+        let rebuild = state.is_none() || instance_data.is_changed();
+        let state = match state {
+            Some(state) if !rebuild => state.into_inner(),
+            _ => rebuilt.insert(VoxelLodState {
+                octree: VoxelOctree::new(&instance_data.instances),
+                selection: Vec::new(),
+                mesh_edge,
+            }),
+        };
+
+        let selection = state
+            .octree
+            .select(&view, lod.budget.max(1), lod.detail_pixels);
+        // A rebuilt state replaces the reduced instances even if the selection is empty as
+        // before, which happens when the data became empty.
+        if selection != state.selection
+            || rebuild
+            || lod.is_changed()
+            || mesh_edge != state.mesh_edge
+        {
+            let reduced = state
+                .octree
+                .instances(&instance_data.instances, &selection, mesh_edge);
+            state.selection = selection;
+            state.mesh_edge = mesh_edge;
+            commands.entity(entity).insert(reduced);
+        }
+        if let Some(state) = rebuilt {
+            commands.entity(entity).insert(state);
+        }
+    }
+}
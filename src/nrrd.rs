@@ -0,0 +1,603 @@
+//! Loader for NRRD volumes (CT/MRI scans) with raw, gzip and ascii encodings.
+
+use crate::{Colormap, InstanceData, WindowLevel};
+use bevy::prelude::*;
+use flate2::read::GzDecoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Errors that can occur while reading a NRRD file.
+#[derive(Debug)]
+pub enum NrrdError {
+    Io(std::io::Error),
+    /// The file does not start with the `NRRD000X` magic.
+    NotNrrd,
+    /// A required header field is missing.
+    MissingField(&'static str),
+    /// A header field could not be parsed.
+    InvalidField(String, String),
+    /// The header uses a type, encoding or dimension that is not supported.
+    Unsupported(String),
+    /// The data section holds fewer samples than announced by `sizes`.
+    Truncated {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for NrrdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NrrdError::Io(err) => write!(f, "io error: {err}"),
+            NrrdError::NotNrrd => write!(f, "missing NRRD magic"),
+            NrrdError::MissingField(field) => write!(f, "missing header field `{field}`"),
+            NrrdError::InvalidField(field, value) => {
+                write!(f, "invalid value `{value}` for header field `{field}`")
+            }
+            NrrdError::Unsupported(what) => write!(f, "unsupported {what}"),
+            NrrdError::Truncated { expected, found } => {
+                write!(f, "expected {expected} samples, found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NrrdError {}
+
+impl From<std::io::Error> for NrrdError {
+    fn from(err: std::io::Error) -> Self {
+        NrrdError::Io(err)
+    }
+}
+
+/// A scalar volume read from a NRRD file.
+#[derive(Clone, Debug)]
+pub struct NrrdVolume {
+    /// Number of samples along each axis, fastest axis first.
+    pub sizes: [usize; 3],
+    /// World-space step between neighbouring samples along each axis
+    /// (`space directions`, or `spacings` along x, y and z).
+    pub directions: [Vec3; 3],
+    /// World-space position of the first sample (`space origin`).
+    pub origin: Vec3,
+    /// Samples converted to `f32`, fastest axis first.
+    pub data: Vec<f32>,
+}
+
+/// Maps volume intensities onto voxel color and opacity.
+#[derive(Clone, Copy, Debug)]
+pub struct VolumeMapping {
+    /// Intensity window, the full data range is used if `None`.
+    pub window: Option<WindowLevel>,
+    pub colormap: Colormap,
+    /// Opacity of voxels at the top of the window, scaled down linearly below it.
+    pub opacity: f32,
+    /// Voxels whose normalized intensity is at or below this value are skipped.
+    pub cutoff: f32,
+}
+
+impl Default for VolumeMapping {
+    fn default() -> Self {
+        Self {
+            window: None,
+            colormap: Colormap::Grayscale,
+            opacity: 1.0,
+            cutoff: 0.0,
+        }
+    }
+}
+
+/// Binary sample types shared by the NRRD, PLY and PCD readers.
+#[derive(Clone, Copy)]
+pub(crate) enum SampleType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+}
+
+impl SampleType {
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "signed char" | "int8" | "int8_t" => SampleType::I8,
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => SampleType::U8,
+            "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+                SampleType::I16
+            }
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+                SampleType::U16
+            }
+            "int" | "signed int" | "int32" | "int32_t" => SampleType::I32,
+            "uint" | "unsigned int" | "uint32" | "uint32_t" => SampleType::U32,
+            "longlong"
+            | "long long"
+            | "long long int"
+            | "signed long long"
+            | "signed long long int"
+            | "int64"
+            | "int64_t" => SampleType::I64,
+            "ulonglong"
+            | "unsigned long long"
+            | "unsigned long long int"
+            | "uint64"
+            | "uint64_t" => SampleType::U64,
+            "float" => SampleType::F32,
+            "double" => SampleType::F64,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            SampleType::I8 | SampleType::U8 => 1,
+            SampleType::I16 | SampleType::U16 => 2,
+            SampleType::I32 | SampleType::U32 | SampleType::F32 => 4,
+            SampleType::I64 | SampleType::U64 | SampleType::F64 => 8,
+        }
+    }
+
+    pub(crate) fn decode(&self, bytes: &[u8], big_endian: bool) -> f32 {
+        macro_rules! read {
+            ($t:ty) => {{
+                let raw = bytes.try_into().unwrap();
+                if big_endian {
+                    <$t>::from_be_bytes(raw) as f32
+                } else {
+                    <$t>::from_le_bytes(raw) as f32
+                }
+            }};
+        }
+        match self {
+            SampleType::I8 => bytes[0] as i8 as f32,
+            SampleType::U8 => bytes[0] as f32,
+            SampleType::I16 => read!(i16),
+            SampleType::U16 => read!(u16),
+            SampleType::I32 => read!(i32),
+            SampleType::U32 => read!(u32),
+            SampleType::I64 => read!(i64),
+            SampleType::U64 => read!(u64),
+            SampleType::F32 => read!(f32),
+            SampleType::F64 => read!(f64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Raw,
+    Gzip,
+    Ascii,
+}
+
+fn parse_floats(field: &str, value: &str) -> Result<Vec<f32>, NrrdError> {
+    value
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<f32>()
+                .map_err(|_| NrrdError::InvalidField(field.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Parses a vector like `(1,0,0)`.
+fn parse_vector(field: &str, value: &str) -> Result<Vec3, NrrdError> {
+    let invalid = || NrrdError::InvalidField(field.to_string(), value.to_string());
+    let inner = value
+        .trim()
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .ok_or_else(invalid)?;
+    let components = inner
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match components.as_slice() {
+        [x, y, z] => Ok(Vec3::new(*x, *y, *z)),
+        [x, y] => Ok(Vec3::new(*x, *y, 0.0)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Loads a NRRD volume from `path`. Detached headers (`.nhdr` with `data file`) are supported.
+pub fn load_nrrd(path: impl AsRef<Path>) -> Result<NrrdVolume, NrrdError> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("NRRD000") {
+        return Err(NrrdError::NotNrrd);
+    }
+
+    let mut sample_type = None;
+    let mut dimension = None;
+    let mut sizes = None;
+    let mut spacings = None;
+    let mut directions = None;
+    let mut origin = Vec3::ZERO;
+    let mut encoding = Encoding::Raw;
+    let mut big_endian = false;
+    let mut byte_skip = 0i64;
+    let mut line_skip = 0usize;
+    let mut data_file = None;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let entry = line.trim_end_matches(['\r', '\n']);
+        if entry.is_empty() {
+            break;
+        }
+        if entry.starts_with('#') || entry.contains(":=") {
+            continue;
+        }
+        let Some((field, value)) = entry.split_once(": ") else {
+            return Err(NrrdError::InvalidField(entry.to_string(), String::new()));
+        };
+        let value = value.trim();
+        let invalid = || NrrdError::InvalidField(field.to_string(), value.to_string());
+        match field {
+            "type" => {
+                sample_type = Some(
+                    SampleType::parse(value)
+                        .ok_or_else(|| NrrdError::Unsupported(format!("type `{value}`")))?,
+                );
+            }
+            "dimension" => dimension = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "sizes" => {
+                sizes = Some(
+                    value
+                        .split_whitespace()
+                        .map(|v| v.parse::<usize>().map_err(|_| invalid()))
+                        .collect::<Result<Vec<_>, _>>()?,
+                );
+            }
+            "spacings" => spacings = Some(parse_floats(field, value)?),
+            "space directions" => {
+                directions = Some(
+                    value
+                        .split_whitespace()
+                        .map(|v| {
+                            if v == "none" {
+                                Ok(None)
+                            } else {
+                                parse_vector(field, v).map(Some)
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                );
+            }
+            "space origin" => origin = parse_vector(field, value)?,
+            "encoding" => {
+                encoding = match value {
+                    "raw" => Encoding::Raw,
+                    "gzip" | "gz" => Encoding::Gzip,
+                    "ascii" | "text" | "txt" => Encoding::Ascii,
+                    _ => return Err(NrrdError::Unsupported(format!("encoding `{value}`"))),
+                };
+            }
+            "endian" => big_endian = value == "big",
+            "byte skip" | "byteskip" => byte_skip = value.parse().map_err(|_| invalid())?,
+            "line skip" | "lineskip" => line_skip = value.parse().map_err(|_| invalid())?,
+            "data file" | "datafile" => data_file = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    let sample_type = sample_type.ok_or(NrrdError::MissingField("type"))?;
+    let dimension = dimension.ok_or(NrrdError::MissingField("dimension"))?;
+    let sizes = sizes.ok_or(NrrdError::MissingField("sizes"))?;
+    if !(1..=3).contains(&dimension) || sizes.len() != dimension {
+        return Err(NrrdError::Unsupported(format!("dimension {dimension}")));
+    }
+    let invalid_sizes = || {
+        let sizes: Vec<String> = sizes.iter().map(usize::to_string).collect();
+        NrrdError::InvalidField("sizes".to_string(), sizes.join(" "))
+    };
+    if sizes.contains(&0) {
+        return Err(invalid_sizes());
+    }
+
+    let mut volume_sizes = [1; 3];
+    volume_sizes[..dimension].copy_from_slice(&sizes);
+    let mut volume_directions = [Vec3::X, Vec3::Y, Vec3::Z];
+    if let Some(directions) = directions {
+        for (axis, direction) in directions.into_iter().take(3).enumerate() {
+            if let Some(direction) = direction {
+                volume_directions[axis] = direction;
+            }
+        }
+    } else if let Some(spacings) = spacings {
+        for (axis, spacing) in spacings.into_iter().take(3).enumerate() {
+            if spacing.is_finite() {
+                volume_directions[axis] *= spacing;
+            }
+        }
+    }
+
+    let mut data_reader: Box<dyn BufRead> = match data_file {
+        Some(data_file) if data_file != "LIST" && !data_file.contains('%') => {
+            let data_path = path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(data_file);
+            Box::new(BufReader::new(File::open(data_path)?))
+        }
+        Some(data_file) => {
+            return Err(NrrdError::Unsupported(format!("data file `{data_file}`")));
+        }
+        None => Box::new(reader),
+    };
+    for _ in 0..line_skip {
+        line.clear();
+        data_reader.read_line(&mut line)?;
+    }
+
+    // Sizes come from the header, so the number of samples and bytes may overflow.
+    let count = volume_sizes
+        .iter()
+        .try_fold(1usize, |count, &size| count.checked_mul(size))
+        .ok_or_else(invalid_sizes)?;
+    let needed = count
+        .checked_mul(sample_type.size())
+        .ok_or_else(invalid_sizes)?;
+    let data = match encoding {
+        Encoding::Ascii => {
+            let mut text = String::new();
+            data_reader.read_to_string(&mut text)?;
+            let data = text
+                .split_whitespace()
+                .take(count)
+                .map(|v| {
+                    v.parse::<f32>()
+                        .map_err(|_| NrrdError::InvalidField("data".to_string(), v.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if data.len() < count {
+                return Err(NrrdError::Truncated {
+                    expected: count,
+                    found: data.len(),
+                });
+            }
+            data
+        }
+        Encoding::Raw | Encoding::Gzip => {
+            // Read what is there rather than allocating what the header announces.
+            let mut bytes = Vec::new();
+            if encoding == Encoding::Gzip {
+                GzDecoder::new(data_reader).read_to_end(&mut bytes)?;
+            } else {
+                data_reader.read_to_end(&mut bytes)?;
+            }
+            let skip = if byte_skip < 0 {
+                bytes.len().saturating_sub(needed)
+            } else {
+                byte_skip as usize
+            };
+            let bytes = bytes.get(skip..).unwrap_or_default();
+            if bytes.len() < needed {
+                return Err(NrrdError::Truncated {
+                    expected: count,
+                    found: bytes.len() / sample_type.size(),
+                });
+            }
+            bytes[..needed]
+                .chunks_exact(sample_type.size())
+                .map(|sample| sample_type.decode(sample, big_endian))
+                .collect()
+        }
+    };
+
+    Ok(NrrdVolume {
+        sizes: volume_sizes,
+        directions: volume_directions,
+        origin,
+        data,
+    })
+}
+
+impl NrrdVolume {
+    /// Distance between neighbouring samples along each axis.
+    ///
+    /// Use this as the size of the voxel mesh, e.g. `Cuboid::from_size(volume.spacing())`.
+    pub fn spacing(&self) -> Vec3 {
+        Vec3::new(
+            self.directions[0].length(),
+            self.directions[1].length(),
+            self.directions[2].length(),
+        )
+    }
+
+    /// Sample at index `(i, j, k)`.
+    pub fn value(&self, i: usize, j: usize, k: usize) -> f32 {
+        self.data[i + self.sizes[0] * (j + self.sizes[1] * k)]
+    }
+
+    /// World-space position of the sample at index `(i, j, k)`.
+    pub fn position(&self, i: usize, j: usize, k: usize) -> Vec3 {
+        self.origin
+            + self.directions[0] * i as f32
+            + self.directions[1] * j as f32
+            + self.directions[2] * k as f32
+    }
+
+    /// Minimum and maximum finite sample value.
+    pub fn range(&self) -> (f32, f32) {
+        self.data
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            })
+    }
+
+    /// Converts the volume into voxel instances with unit scale, skipping voxels below the cutoff
+    /// and non-finite samples.
+    ///
+    /// Positions follow the spacing of the volume, but the size of the voxels is that of the
+    /// plot's mesh, which should therefore be `Cuboid::from_size(volume.spacing())`.
+    pub fn to_instances(&self, mapping: &VolumeMapping) -> Vec<InstanceData> {
+        let window = mapping.window.unwrap_or_else(|| {
+            let (min, max) = self.range();
+            WindowLevel::from_range(min, max)
+        });
+
+        let mut instances = Vec::new();
+        for k in 0..self.sizes[2] {
+            for j in 0..self.sizes[1] {
+                for i in 0..self.sizes[0] {
+                    let value = self.value(i, j, k);
+                    if !value.is_finite() {
+                        continue;
+                    }
+                    let t = window.normalize(value);
+                    if t.is_nan() || t <= mapping.cutoff {
+                        continue;
+                    }
+                    let color = mapping.colormap.sample(t).with_alpha(mapping.opacity * t);
+                    instances.push(InstanceData {
+                        position: self.position(i, j, k).to_array(),
+                        scale: 1.0,
+                        color: LinearRgba::from(color).to_f32_array(),
+                    });
+                }
+            }
+        }
+        instances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const SAMPLES: [f32; 12] = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0];
+
+    /// Writes a NRRD file with the given header fields and data, and loads it.
+    fn load(name: &str, fields: &str, data: &[u8]) -> Result<NrrdVolume, NrrdError> {
+        let path = std::env::temp_dir().join(format!("bevy_voxel_plot_{name}.nrrd"));
+        let mut file = File::create(&path).unwrap();
+        write!(file, "NRRD0004\ntype: float\n{fields}\n\n").unwrap();
+        file.write_all(data).unwrap();
+        drop(file);
+        let volume = load_nrrd(&path);
+        std::fs::remove_file(path).unwrap();
+        volume
+    }
+
+    fn raw() -> Vec<u8> {
+        SAMPLES.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn raw_and_gzip() {
+        let volume = load(
+            "raw",
+            "dimension: 3\nsizes: 2 3 2\nencoding: raw\nendian: little",
+            &raw(),
+        )
+        .unwrap();
+        assert_eq!(volume.sizes, [2, 3, 2]);
+        assert_eq!(volume.data, SAMPLES);
+        assert_eq!(volume.value(1, 2, 1), 11.0);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&raw()).unwrap();
+        let volume = load(
+            "gzip",
+            "dimension: 3\nsizes: 2 3 2\nencoding: gzip",
+            &encoder.finish().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(volume.data, SAMPLES);
+    }
+
+    #[test]
+    fn spacings_and_directions() {
+        let volume = load(
+            "spacings",
+            "dimension: 3\nsizes: 2 3 2\nspacings: 0.5 2 3",
+            &raw(),
+        )
+        .unwrap();
+        assert_eq!(volume.spacing(), Vec3::new(0.5, 2.0, 3.0));
+
+        // Space directions take precedence over spacings.
+        let volume = load(
+            "directions",
+            "dimension: 3\nsizes: 2 3 2\nspacings: 5 5 5\n\
+             space directions: (0,0.3,0) (0.3,0,0) (0,0,1)\nspace origin: (1,2,3)",
+            &raw(),
+        )
+        .unwrap();
+        assert_eq!(
+            volume.directions,
+            [Vec3::new(0.0, 0.3, 0.0), Vec3::new(0.3, 0.0, 0.0), Vec3::Z]
+        );
+        assert_eq!(volume.position(1, 1, 1), Vec3::new(1.3, 2.3, 4.0));
+    }
+
+    #[test]
+    fn byte_skip() {
+        let mut data = vec![0xff; 7];
+        data.extend(raw());
+        let volume = load("skip", "dimension: 3\nsizes: 2 3 2\nbyte skip: -1", &data).unwrap();
+        assert_eq!(volume.data, SAMPLES);
+        let volume = load("skip7", "dimension: 3\nsizes: 2 3 2\nbyte skip: 7", &data).unwrap();
+        assert_eq!(volume.data, SAMPLES);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = raw();
+        assert!(matches!(
+            load("truncated", "dimension: 3\nsizes: 2 3 2", &data[..40]),
+            Err(NrrdError::Truncated {
+                expected: 12,
+                found: 10
+            })
+        ));
+    }
+
+    #[test]
+    fn hostile_sizes() {
+        for sizes in [
+            "18446744073709551615 2 1",
+            "4294967296 4294967296 2",
+            "4611686018427387904 1 1",
+            "4 4 0",
+        ] {
+            let fields = format!("dimension: 3\nsizes: {sizes}");
+            assert!(matches!(
+                load("hostile", &fields, &raw()),
+                Err(NrrdError::InvalidField(field, _)) if field == "sizes"
+            ));
+        }
+    }
+
+    #[test]
+    fn non_finite_samples() {
+        let volume = NrrdVolume {
+            sizes: [3, 1, 1],
+            directions: [Vec3::X, Vec3::Y, Vec3::Z],
+            origin: Vec3::ZERO,
+            data: vec![1.0, f32::NAN, 2.0],
+        };
+        let instances = volume.to_instances(&VolumeMapping::default());
+        assert_eq!(instances.len(), 1);
+        assert!(instances[0].color.iter().all(|c| c.is_finite()));
+    }
+}
@@ -0,0 +1,266 @@
+//! Ambient occlusion of voxel grids, computed on the CPU from neighbour occupancy.
+
+use crate::lod::loaded_mesh_edge;
+use crate::{InstanceData, InstanceMaterialData};
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+
+/// Largest number of grid cells per instance for which occupancy is stored densely.
+const MAX_DENSE_CELLS_PER_INSTANCE: usize = 64;
+
+/// Largest grid coordinate, leaving room for neighbour offsets and grid sizes in `i32`.
+const MAX_CELL: f32 = (1 << 30) as f32;
+
+/// Darkens the corners of voxels by how many of their neighbours in a dense grid are occupied,
+/// like Minecraft's smooth lighting, giving depth cues without lighting.
+///
+/// Which of the 26 surrounding cells of every instance are occupied is computed on the CPU
+/// whenever the plot's instances change, and the shader shades each corner of each face from the
+/// three cells in front of it. Instances are assumed to lie on a grid of `spacing`, by default the
+/// largest edge of the plot's mesh, and count as occupied if their opacity is at least
+/// `min_alpha`. Works best for opaque volumes of cube voxels.
+///
+/// Nothing is shaded until the mesh is loaded, and instances too far from the others to be
+/// placed on the grid are left unshaded.
+#[derive(Component, Clone, Debug)]
+pub struct VoxelAmbientOcclusion {
+    /// Darkening of a fully occluded corner, from 0 to 1.
+    pub strength: f32,
+    /// Grid spacing, `None` for the largest edge of the plot's mesh. Spacings that are not
+    /// positive and finite are ignored as well.
+    pub spacing: Option<f32>,
+    pub min_alpha: f32,
+    /// Occupied neighbours of every instance, see `neighbour_bit`.
+    neighbours: Vec<u32>,
+}
+
+impl Default for VoxelAmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            strength: 0.6,
+            spacing: None,
+            min_alpha: 0.5,
+            neighbours: Vec::new(),
+        }
+    }
+}
+
+impl VoxelAmbientOcclusion {
+    pub fn new(strength: f32) -> Self {
+        Self {
+            strength,
+            ..default()
+        }
+    }
+
+    /// Sets the grid spacing, keeping the mesh's edge if `spacing` is not positive and finite.
+    pub fn with_spacing(mut self, spacing: f32) -> Self {
+        self.spacing = valid_spacing(spacing);
+        self
+    }
+
+    pub fn with_min_alpha(mut self, min_alpha: f32) -> Self {
+        self.min_alpha = min_alpha;
+        self
+    }
+
+    /// Occupied neighbours of the instance at `index`, one bit per cell of the surrounding
+    /// 3x3x3 block.
+    pub(crate) fn neighbours(&self, index: usize) -> u32 {
+        self.neighbours.get(index).copied().unwrap_or(0)
+    }
+}
+
+fn valid_spacing(spacing: f32) -> Option<f32> {
+    (spacing.is_finite() && spacing > 0.0).then_some(spacing)
+}
+
+/// Bit of the neighbour at an offset of -1, 0 or 1 cells along each axis.
+fn neighbour_bit(offset: IVec3) -> u32 {
+    let index = (offset.x + 1) + 3 * (offset.y + 1) + 9 * (offset.z + 1);
+    1 << index
+}
+
+/// Occupied cells of a grid.
+enum Occupancy {
+    Dense {
+        min: IVec3,
+        size: IVec3,
+        bits: Vec<u64>,
+    },
+    /// Grids too sparse to store densely.
+    Sparse(HashSet<IVec3>),
+}
+
+impl Occupancy {
+    fn new(cells: &[IVec3]) -> Self {
+        let (min, max) = cells
+            .iter()
+            .fold((IVec3::MAX, IVec3::MIN), |(min, max), &cell| {
+                (min.min(cell), max.max(cell))
+            });
+        let volume = (max.as_i64vec3() - min.as_i64vec3() + 1)
+            .as_dvec3()
+            .element_product();
+        if volume > (cells.len() * MAX_DENSE_CELLS_PER_INSTANCE) as f64 {
+            return Occupancy::Sparse(cells.iter().copied().collect());
+        }
+        let size = max - min + 1;
+        let mut bits = vec![0u64; (volume as usize).div_ceil(64)];
+        for &cell in cells {
+            let index = Self::dense_index(cell - min, size);
+            bits[index / 64] |= 1 << (index % 64);
+        }
+        Occupancy::Dense { min, size, bits }
+    }
+
+    /// Index of a cell relative to `min`, all of whose coordinates lie within `size`.
+    fn dense_index(cell: IVec3, size: IVec3) -> usize {
+        let (cell, size) = (cell.as_uvec3(), size.as_uvec3());
+        cell.x as usize + size.x as usize * (cell.y as usize + size.y as usize * cell.z as usize)
+    }
+
+    fn contains(&self, cell: IVec3) -> bool {
+        match self {
+            Occupancy::Dense { min, size, bits } => {
+                let cell = cell - *min;
+                if cell.cmplt(IVec3::ZERO).any() || cell.cmpge(*size).any() {
+                    return false;
+                }
+                let index = Self::dense_index(cell, *size);
+                bits[index / 64] & (1 << (index % 64)) != 0
+            }
+            Occupancy::Sparse(cells) => cells.contains(&cell),
+        }
+    }
+}
+
+/// Occupied neighbours of every instance on a grid of `spacing`.
+fn occupied_neighbours(instances: &[InstanceData], spacing: f32, min_alpha: f32) -> Vec<u32> {
+    let origin = instances.iter().fold(Vec3::MAX, |origin, instance| {
+        origin.min(Vec3::from_array(instance.position))
+    });
+    // Cells lie within `0..=MAX_CELL`, instances outside of it are left out of the grid.
+    let cell = |instance: &InstanceData| {
+        let cell = ((Vec3::from_array(instance.position) - origin) / spacing).round();
+        (cell.cmpge(Vec3::ZERO).all() && cell.cmple(Vec3::splat(MAX_CELL)).all())
+            .then(|| cell.as_ivec3())
+    };
+    let occupied: Vec<IVec3> = instances
+        .iter()
+        .filter(|instance| instance.color[3] >= min_alpha)
+        .filter_map(cell)
+        .collect();
+    if occupied.is_empty() {
+        return vec![0; instances.len()];
+    }
+    let occupancy = Occupancy::new(&occupied);
+    instances
+        .iter()
+        .map(|instance| {
+            let Some(center) = cell(instance) else {
+                return 0;
+            };
+            let mut neighbours = 0;
+            for z in -1..=1 {
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        let offset = IVec3::new(x, y, z);
+                        if offset != IVec3::ZERO && occupancy.contains(center + offset) {
+                            neighbours |= neighbour_bit(offset);
+                        }
+                    }
+                }
+            }
+            neighbours
+        })
+        .collect()
+}
+
+/// Recomputes the occupied neighbours of plots whose instances, mesh or settings changed.
+pub(crate) fn update_voxel_ambient_occlusion(
+    mut plots: Query<(
+        Ref<InstanceMaterialData>,
+        Ref<Mesh3d>,
+        &mut VoxelAmbientOcclusion,
+    )>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (instance_data, mesh, mut occlusion) in &mut plots {
+        let computed = occlusion.neighbours.len() == instance_data.instances.len();
+        if computed && !instance_data.is_changed() && !mesh.is_changed() && !occlusion.is_changed()
+        {
+            continue;
+        }
+        // The mesh's edge is the default spacing, wait for it even if the spacing is given so
+        // that the result does not depend on when the mesh loads.
+        let Some(mesh_edge) = loaded_mesh_edge(&meshes, &mesh) else {
+            if !occlusion.neighbours.is_empty() {
+                occlusion.neighbours.clear();
+            }
+            continue;
+        };
+        let Some(spacing) = occlusion
+            .spacing
+            .and_then(valid_spacing)
+            .or_else(|| valid_spacing(mesh_edge))
+        else {
+            occlusion.neighbours.clear();
+            continue;
+        };
+        let neighbours =
+            occupied_neighbours(&instance_data.instances, spacing, occlusion.min_alpha);
+        // Marks the settings as changed, which extraction looks at, also when only the mesh did.
+        occlusion.neighbours = neighbours;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voxel(position: [f32; 3]) -> InstanceData {
+        InstanceData {
+            position,
+            scale: 1.0,
+            color: [1.0; 4],
+        }
+    }
+
+    #[test]
+    fn neighbours() {
+        let instances = [
+            voxel([0.0, 0.0, 0.0]),
+            voxel([1.0, 0.0, 0.0]),
+            voxel([1.0, 1.0, 1.0]),
+            voxel([5.0, 0.0, 0.0]),
+        ];
+        let neighbours = occupied_neighbours(&instances, 1.0, 0.5);
+        assert_eq!(
+            neighbours[0],
+            neighbour_bit(IVec3::X) | neighbour_bit(IVec3::ONE)
+        );
+        assert_eq!(
+            neighbours[1],
+            neighbour_bit(IVec3::NEG_X) | neighbour_bit(IVec3::new(0, 1, 1))
+        );
+        assert_eq!(neighbours[3], 0);
+    }
+
+    #[test]
+    fn degenerate_grids() {
+        let instances = [
+            voxel([0.0, 0.0, 0.0]),
+            voxel([1.0, 1.0, 1.0]),
+            voxel([1e30, -1e30, 0.0]),
+            voxel([f32::NAN, 0.0, 0.0]),
+        ];
+        for spacing in [1e-30, 1.0, f32::MIN_POSITIVE] {
+            assert_eq!(occupied_neighbours(&instances, spacing, 0.5).len(), 4);
+        }
+        assert_eq!(
+            VoxelAmbientOcclusion::default().with_spacing(0.0).spacing,
+            None
+        );
+    }
+}
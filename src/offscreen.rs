@@ -0,0 +1,233 @@
+//! Offscreen rendering of plots into images, read back to the CPU and saved to disk.
+
+use crate::{FrameSequence, VoxelTimeSeries};
+use bevy::app::{PluginGroupBuilder, ScheduleRunnerPlugin};
+use bevy::camera::RenderTarget;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureFormat};
+use bevy::render::settings::{RenderCreation, WgpuSettings};
+use bevy::render::view::screenshot::{save_to_disk, Screenshot, ScreenshotCaptured};
+use bevy::render::RenderPlugin;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// `DefaultPlugins` set up for rendering without a window, e.g. in batch jobs and CI.
+///
+/// There is no window or event loop, the app updates as fast as possible and pipelines are
+/// compiled synchronously so the first frames are complete. Pass
+/// `WgpuSettings { force_fallback_adapter: true, ..default() }` to render on a software adapter
+/// when no GPU is available.
+pub fn headless_plugins(settings: WgpuSettings) -> PluginGroupBuilder {
+    DefaultPlugins
+        .build()
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+            ..default()
+        })
+        .set(RenderPlugin {
+            render_creation: RenderCreation::Automatic(settings),
+            synchronous_pipeline_compilation: true,
+            ..default()
+        })
+        .disable::<WinitPlugin>()
+        .add(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CaptureState {
+    /// Rendering the given number of frames before capturing.
+    Warmup(u32),
+    /// Waiting for the frame to be read back.
+    Pending,
+    Done,
+}
+
+/// Renders a 3D camera offscreen into an image of a fixed resolution and reads a frame back to
+/// the CPU, triggering `OffscreenFrame` on the camera and saving it to `path` if set.
+///
+/// A capture is taken after `warmup_frames`, so camera fitting, level of detail and budgets have
+/// settled, and can be requested again with `capture`. Warmup does not start before the time
+/// series following the camera's `FrameSequence` show its frame.
+#[derive(Component, Clone, Debug)]
+#[require(Camera3d)]
+pub struct OffscreenRender {
+    /// Resolution in physical pixels.
+    pub size: UVec2,
+    pub background: Color,
+    /// File the frame is saved to, in the format of its extension, e.g. `plot.png`.
+    pub path: Option<PathBuf>,
+    /// Frames rendered before a capture.
+    pub warmup_frames: u32,
+    /// Whether to exit the app once every `OffscreenRender` is done.
+    pub exit_when_done: bool,
+    image: Option<Handle<Image>>,
+    state: CaptureState,
+    captures: u32,
+}
+
+impl OffscreenRender {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            size: UVec2::new(width, height),
+            background: Color::BLACK,
+            path: None,
+            warmup_frames: 3,
+            exit_when_done: false,
+            image: None,
+            state: CaptureState::Warmup(0),
+            captures: 0,
+        }
+    }
+
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_exit(mut self) -> Self {
+        self.exit_when_done = true;
+        self
+    }
+
+    /// Captures another frame after the warmup, e.g. after moving the camera.
+    pub fn capture(&mut self) {
+        self.state = CaptureState::Warmup(0);
+    }
+
+    /// Whether the last requested frame has been captured.
+    pub fn is_done(&self) -> bool {
+        self.state == CaptureState::Done
+    }
+
+    /// Number of frames captured so far.
+    pub fn captures(&self) -> u32 {
+        self.captures
+    }
+
+    /// The image the camera renders into, once created.
+    pub fn image(&self) -> Option<&Handle<Image>> {
+        self.image.as_ref()
+    }
+}
+
+/// Triggered on a camera with `OffscreenRender` when a frame was read back.
+#[derive(EntityEvent, Clone, Debug)]
+pub struct OffscreenFrame {
+    pub entity: Entity,
+    /// Index of the capture, counting from zero.
+    pub index: u32,
+    /// The rendered frame, with its pixels in `data`.
+    pub image: Image,
+}
+
+/// Creates the render targets of offscreen renders, requests their captures and exits the app
+/// once they are done if asked to.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_offscreen_renders(
+    mut commands: Commands,
+    mut renders: Query<(
+        Entity,
+        &mut OffscreenRender,
+        &mut Camera,
+        Option<&RenderTarget>,
+        Option<&FrameSequence>,
+    )>,
+    time_series: Query<&VoxelTimeSeries>,
+    mut images: ResMut<Assets<Image>>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    for (entity, mut render, mut camera, target, sequence) in &mut renders {
+        let size = render.size.max(UVec2::ONE);
+        let image = match render.image.clone() {
+            Some(image) => {
+                if let Some(image) = images.get_mut(&image) {
+                    if image.size() != size {
+                        image.resize(Extent3d {
+                            width: size.x,
+                            height: size.y,
+                            depth_or_array_layers: 1,
+                        });
+                    }
+                }
+                image
+            }
+            None => {
+                let image = images.add(Image::new_target_texture(
+                    size.x,
+                    size.y,
+                    TextureFormat::Rgba8UnormSrgb,
+                    None,
+                ));
+                render.image = Some(image.clone());
+                image
+            }
+        };
+        if !matches!(target, Some(RenderTarget::Image(target)) if target.handle == image) {
+            commands
+                .entity(entity)
+                .insert(RenderTarget::Image(image.clone().into()));
+        }
+        let clear_color = ClearColorConfig::Custom(render.background);
+        if !matches!((&camera.clear_color, &clear_color), (ClearColorConfig::Custom(a), ClearColorConfig::Custom(b)) if a == b)
+        {
+            camera.clear_color = clear_color;
+        }
+
+        // Hold the capture while time series show a frame other than the sequence's.
+        let waiting = sequence.is_some_and(|sequence| {
+            time_series
+                .iter()
+                .any(|series| series.sequence == Some(entity) && !series.is_ready_for(sequence))
+        });
+        match render.state {
+            CaptureState::Warmup(_) if waiting => {
+                render.state = CaptureState::Warmup(0);
+            }
+            CaptureState::Warmup(frames) if frames < render.warmup_frames => {
+                render.state = CaptureState::Warmup(frames + 1);
+            }
+            CaptureState::Warmup(_) => {
+                render.state = CaptureState::Pending;
+                let index = render.captures;
+                let mut save = render.path.clone().map(save_to_disk);
+                commands.spawn(Screenshot::image(image)).observe(
+                    move |captured: On<ScreenshotCaptured>,
+                          mut commands: Commands,
+                          mut renders: Query<&mut OffscreenRender>| {
+                        let image = captured.image.clone();
+                        if let Some(save) = save.as_mut() {
+                            save(captured);
+                        }
+                        commands.trigger(OffscreenFrame {
+                            entity,
+                            index,
+                            image,
+                        });
+                        if let Ok(mut render) = renders.get_mut(entity) {
+                            if render.state == CaptureState::Pending {
+                                render.state = CaptureState::Done;
+                                render.captures += 1;
+                            }
+                        }
+                    },
+                );
+            }
+            CaptureState::Pending | CaptureState::Done => {}
+        }
+    }
+
+    if renders.iter().any(|(_, render, ..)| render.exit_when_done)
+        && renders.iter().all(|(_, render, ..)| render.is_done())
+    {
+        exit.write(AppExit::Success);
+    }
+}
@@ -0,0 +1,374 @@
+//! Ray-cast picking of individual voxels.
+//!
+//! Instances of pickable plots are stored in a bounding volume hierarchy over their axis aligned
+//! boxes, which is rebuilt whenever the data or the mesh changes.
+
+use crate::{InstanceData, InstanceMaterialData, VoxelClip};
+use bevy::camera::primitives::MeshAabb;
+use bevy::camera::{NormalizedRenderTarget, RenderTarget};
+use bevy::ecs::query::QueryFilter;
+use bevy::ecs::system::SystemParam;
+use bevy::math::Ray3d;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+/// Instances per leaf of the bounding volume hierarchy.
+const LEAF_INSTANCES: usize = 4;
+/// Cursor movement in logical pixels between press and release up to which a click is reported.
+const CLICK_TOLERANCE: f32 = 4.0;
+
+/// Makes the instances of an entity with `InstanceMaterialData` pickable with the cursor.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelPickable {
+    /// Instances with a lower opacity are ignored by the ray-cast.
+    pub min_alpha: f32,
+}
+
+impl Default for VoxelPickable {
+    fn default() -> Self {
+        Self { min_alpha: 0.05 }
+    }
+}
+
+/// Cursor position in the viewport of a camera, for cameras that do not render to a window.
+///
+/// Cameras rendering to an image, e.g. one shown in an egui panel, need this to be updated with
+/// the cursor position in logical pixels of the render target (physical pixels divided by the
+/// target's scale factor), or `None` when the cursor is outside of the image.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelPickingCursor(pub Option<Vec2>);
+
+/// Triggered on a plot when one of its voxels is clicked.
+#[derive(EntityEvent, Clone, Copy, Debug)]
+pub struct VoxelClick {
+    /// The plot entity.
+    pub entity: Entity,
+    /// The camera through which the voxel was clicked.
+    pub camera: Entity,
+    /// Index of the instance in `InstanceMaterialData::instances`.
+    pub index: usize,
+    pub instance: InstanceData,
+    /// Point where the ray enters the voxel's bounding box, in world space.
+    pub point: Vec3,
+}
+
+/// Closest voxel hit by a ray.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct VoxelHit {
+    pub(crate) entity: Entity,
+    pub(crate) index: usize,
+    pub(crate) distance: f32,
+}
+
+struct BvhNode {
+    min: Vec3,
+    max: Vec3,
+    /// First instance in `InstanceBvh::order` for leaves, index of the first child otherwise.
+    start: u32,
+    /// Number of instances of a leaf, zero for inner nodes.
+    count: u32,
+}
+
+/// Bounding volume hierarchy over the instance boxes of a plot.
+#[derive(Component)]
+pub(crate) struct InstanceBvh {
+    nodes: Vec<BvhNode>,
+    order: Vec<u32>,
+    /// Bounding box of the mesh, which is scaled and translated per instance.
+    mesh_center: Vec3,
+    mesh_half_extents: Vec3,
+    min_alpha: f32,
+}
+
+impl InstanceBvh {
+    fn new(
+        instances: &[InstanceData],
+        mesh_center: Vec3,
+        mesh_half_extents: Vec3,
+        min_alpha: f32,
+    ) -> Self {
+        let mut bvh = InstanceBvh {
+            nodes: Vec::new(),
+            order: (0..instances.len() as u32)
+                .filter(|&i| instances[i as usize].color[3] >= min_alpha)
+                .collect(),
+            mesh_center,
+            mesh_half_extents,
+            min_alpha,
+        };
+        if !bvh.order.is_empty() {
+            bvh.nodes.push(BvhNode {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+                start: 0,
+                count: 0,
+            });
+            bvh.build(instances, 0, 0, bvh.order.len());
+        }
+        bvh
+    }
+
+    /// Bounding box of an instance.
+    pub(crate) fn instance_bounds(&self, instance: &InstanceData) -> (Vec3, Vec3) {
+        let center = Vec3::from_array(instance.position) + self.mesh_center * instance.scale;
+        let half_extents = self.mesh_half_extents * instance.scale.abs();
+        (center - half_extents, center + half_extents)
+    }
+
+    fn build(&mut self, instances: &[InstanceData], node: usize, start: usize, end: usize) {
+        let (min, max) = self.order[start..end].iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), &i| {
+                let (lo, hi) = self.instance_bounds(&instances[i as usize]);
+                (min.min(lo), max.max(hi))
+            },
+        );
+        self.nodes[node].min = min;
+        self.nodes[node].max = max;
+        if end - start <= LEAF_INSTANCES {
+            self.nodes[node].start = start as u32;
+            self.nodes[node].count = (end - start) as u32;
+            return;
+        }
+
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let mid = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(mid - start, |&a, &b| {
+            instances[a as usize].position[axis].total_cmp(&instances[b as usize].position[axis])
+        });
+
+        let child = self.nodes.len();
+        for _ in 0..2 {
+            self.nodes.push(BvhNode {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+                start: 0,
+                count: 0,
+            });
+        }
+        self.nodes[node].start = child as u32;
+        self.build(instances, child, start, mid);
+        self.build(instances, child + 1, mid, end);
+    }
+
+    /// Closest instance hit by the ray, as `(index, distance)`, skipping instances hidden by
+    /// the clipping planes.
+    pub(crate) fn cast(
+        &self,
+        instances: &[InstanceData],
+        clip: Option<&VoxelClip>,
+        ray: Ray3d,
+    ) -> Option<(usize, f32)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let origin = ray.origin;
+        let inverse = ray.direction.as_vec3().recip();
+        let mut closest: Option<(usize, f32)> = None;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let limit = closest.map_or(f32::INFINITY, |(_, distance)| distance);
+            if ray_box(origin, inverse, node.min, node.max).is_none_or(|t| t > limit) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start as usize);
+                stack.push(node.start as usize + 1);
+                continue;
+            }
+            for &i in &self.order[node.start as usize..(node.start + node.count) as usize] {
+                let instance = &instances[i as usize];
+                if clip.is_some_and(|clip| !clip.contains(Vec3::from_array(instance.position))) {
+                    continue;
+                }
+                let (min, max) = self.instance_bounds(instance);
+                if let Some(t) = ray_box(origin, inverse, min, max) {
+                    if closest.is_none_or(|(_, distance)| t < distance) {
+                        closest = Some((i as usize, t));
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// Distance along the ray at which it enters the box, zero if it starts inside.
+fn ray_box(origin: Vec3, inverse_direction: Vec3, min: Vec3, max: Vec3) -> Option<f32> {
+    let t1 = (min - origin) * inverse_direction;
+    let t2 = (max - origin) * inverse_direction;
+    let near = t1.min(t2).max_element().max(0.0);
+    let far = t1.max(t2).min_element();
+    (near <= far).then_some(near)
+}
+
+/// Rebuilds the bounding volume hierarchies of pickable plots when their data or mesh changed.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_instance_bvh(
+    mut commands: Commands,
+    plots: Query<(
+        Entity,
+        Ref<InstanceMaterialData>,
+        &VoxelPickable,
+        &Mesh3d,
+        Option<&InstanceBvh>,
+    )>,
+    meshes: Res<Assets<Mesh>>,
+    mut removed: RemovedComponents<VoxelPickable>,
+) {
+    for entity in removed.read() {
+        if let Ok(mut entity) = commands.get_entity(entity) {
+            entity.remove::<InstanceBvh>();
+        }
+    }
+
+    for (entity, instance_data, pickable, mesh, bvh) in &plots {
+        let Some(aabb) = meshes.get(mesh).and_then(|mesh| mesh.compute_aabb()) else {
+            continue;
+        };
+        let (center, half_extents) = (Vec3::from(aabb.center), Vec3::from(aabb.half_extents));
+        let stale = bvh.is_none_or(|bvh| {
+            bvh.mesh_center != center
+                || bvh.mesh_half_extents != half_extents
+                || bvh.min_alpha != pickable.min_alpha
+        });
+        if stale || instance_data.is_changed() {
+            commands.entity(entity).insert(InstanceBvh::new(
+                &instance_data.instances,
+                center,
+                half_extents,
+                pickable.min_alpha,
+            ));
+        }
+    }
+}
+
+/// Query data of a plot that can be ray-cast.
+pub(crate) type PickablePlot = (
+    Entity,
+    &'static InstanceMaterialData,
+    &'static InstanceBvh,
+    Option<&'static VoxelClip>,
+);
+
+/// Casts a ray against the given plots and returns the closest hit.
+pub(crate) fn cast_voxel_ray<F: QueryFilter>(
+    plots: &Query<PickablePlot, F>,
+    ray: Ray3d,
+) -> Option<VoxelHit> {
+    plots
+        .iter()
+        .filter_map(|(entity, instance_data, bvh, clip)| {
+            let (index, distance) = bvh.cast(&instance_data.instances, clip, ray)?;
+            Some(VoxelHit {
+                entity,
+                index,
+                distance,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+/// Ray from the cursor through the 3D camera it hovers.
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub(crate) struct CursorRay<'w, 's> {
+    cameras: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Camera,
+            &'static GlobalTransform,
+            &'static RenderTarget,
+            Option<&'static VoxelPickingCursor>,
+        ),
+        With<Camera3d>,
+    >,
+    windows: Query<'w, 's, (Entity, &'static Window, Has<PrimaryWindow>)>,
+}
+
+impl CursorRay<'_, '_> {
+    /// Returns the camera, the cursor position in its viewport and the ray, preferring the
+    /// camera rendered last.
+    pub(crate) fn get(&self) -> Option<(Entity, Vec2, Ray3d)> {
+        let primary = self
+            .windows
+            .iter()
+            .find_map(|(entity, _, primary)| primary.then_some(entity));
+        let mut cameras: Vec<_> = self
+            .cameras
+            .iter()
+            .filter(|(_, camera, ..)| camera.is_active)
+            .collect();
+        cameras.sort_by_key(|(_, camera, ..)| std::cmp::Reverse(camera.order));
+
+        cameras
+            .into_iter()
+            .find_map(|(entity, camera, transform, target, cursor)| {
+                let position = match (cursor, target.normalize(primary)) {
+                    (Some(cursor), _) => cursor.0?,
+                    (None, Some(NormalizedRenderTarget::Window(window))) => self
+                        .windows
+                        .get(window.entity())
+                        .ok()?
+                        .1
+                        .cursor_position()?,
+                    _ => return None,
+                };
+                if !camera.logical_viewport_rect()?.contains(position) {
+                    return None;
+                }
+                let ray = camera.viewport_to_world(transform, position).ok()?;
+                Some((entity, position, ray))
+            })
+    }
+}
+
+/// Triggers `VoxelClick` when the left mouse button is released without dragging over a voxel.
+pub(crate) fn pick_voxels(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    cursor_ray: CursorRay,
+    plots: Query<PickablePlot>,
+    mut pressed_at: Local<Option<Vec2>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) && !mouse.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some((camera, position, ray)) = cursor_ray.get() else {
+        *pressed_at = None;
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) {
+        *pressed_at = Some(position);
+        return;
+    }
+    let Some(pressed) = pressed_at.take() else {
+        return;
+    };
+    if pressed.distance(position) > CLICK_TOLERANCE {
+        return;
+    }
+
+    if let Some(hit) = cast_voxel_ray(&plots, ray) {
+        let Ok((_, instance_data, ..)) = plots.get(hit.entity) else {
+            return;
+        };
+        commands.trigger(VoxelClick {
+            entity: hit.entity,
+            camera,
+            index: hit.index,
+            instance: instance_data.instances[hit.index],
+            point: ray.get_point(hit.distance),
+        });
+    }
+}
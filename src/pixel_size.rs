@@ -0,0 +1,49 @@
+//! Screen space sizing of voxels and sprites, resolved in the vertex shader.
+
+use bevy::prelude::*;
+
+/// Sizes the instances of a plot in screen pixels instead of world units, like point cloud
+/// viewers do, so distant points stay visible and near points don't fill the screen.
+///
+/// With `size`, an instance with scale one is drawn `size` physical pixels across at any
+/// distance, for voxels measured by the largest edge of the plot's mesh. Either way, instances
+/// are clamped to `min..=max` pixels. The size also replaces the size of `VoxelSprites`. Picking
+/// and hovering still use world sizes.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelPixelSize {
+    /// Edge length in pixels of an instance with scale one, `None` keeps world sizes.
+    pub size: Option<f32>,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for VoxelPixelSize {
+    fn default() -> Self {
+        Self {
+            size: None,
+            min: 0.0,
+            max: f32::MAX,
+        }
+    }
+}
+
+impl VoxelPixelSize {
+    /// Constant size in pixels.
+    pub fn new(size: f32) -> Self {
+        Self {
+            size: Some(size),
+            ..default()
+        }
+    }
+
+    /// World sizes clamped to a range of pixels.
+    pub fn clamped(min: f32, max: f32) -> Self {
+        Self::default().with_clamp(min, max)
+    }
+
+    pub fn with_clamp(mut self, min: f32, max: f32) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+}
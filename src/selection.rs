@@ -0,0 +1,254 @@
+//! Selection of many voxels at once, by world-space box, screen-space rectangle or lasso.
+
+use crate::{InstanceData, InstanceMaterialData, ReducedInstances, VoxelAmbientOcclusion};
+use bevy::prelude::*;
+use bevy::render::{sync_world::RenderEntity, Extract};
+
+/// Set of selected instances of a plot, stored as a bitset over instance indices.
+///
+/// Selected instances are drawn in the selection color. The indices refer to
+/// `InstanceMaterialData::instances`, so the selection should be cleared when the data is
+/// replaced. Aggregated level of detail nodes are not highlighted.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct VoxelSelection {
+    bits: Vec<u64>,
+    /// Color blended over selected instances, by its alpha.
+    pub color: Color,
+}
+
+impl Default for VoxelSelection {
+    fn default() -> Self {
+        Self {
+            bits: Vec::new(),
+            color: Color::srgba(0.0, 0.8, 1.0, 0.8),
+        }
+    }
+}
+
+impl VoxelSelection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(color: Color) -> Self {
+        Self { color, ..default() }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.bits
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        if self.bits.len() <= index / 64 {
+            self.bits.resize(index / 64 + 1, 0);
+        }
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if let Some(word) = self.bits.get_mut(index / 64) {
+            *word &= !(1 << (index % 64));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.clear();
+    }
+
+    /// Number of selected instances.
+    pub fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Iterates over the selected indices in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bits.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| i * 64 + bit)
+        })
+    }
+
+    /// Selects all instances whose position lies within the world-space box.
+    pub fn select_box(&mut self, instances: &[InstanceData], min: Vec3, max: Vec3) {
+        for (i, instance) in instances.iter().enumerate() {
+            let position = Vec3::from_array(instance.position);
+            if position.cmpge(min).all() && position.cmple(max).all() {
+                self.insert(i);
+            }
+        }
+    }
+
+    /// Selects all instances whose position projects into the rectangle, given in logical
+    /// viewport coordinates of the camera.
+    pub fn select_rect(
+        &mut self,
+        instances: &[InstanceData],
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        rect: Rect,
+    ) {
+        self.select_projected(instances, camera, camera_transform, |point| {
+            rect.contains(point)
+        });
+    }
+
+    /// Selects all instances whose position projects into the lasso polygon, given in logical
+    /// viewport coordinates of the camera.
+    pub fn select_lasso(
+        &mut self,
+        instances: &[InstanceData],
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        lasso: &[Vec2],
+    ) {
+        let bounds = lasso
+            .iter()
+            .fold(Rect::EMPTY, |bounds, &point| bounds.union_point(point));
+        self.select_projected(instances, camera, camera_transform, |point| {
+            bounds.contains(point) && polygon_contains(lasso, point)
+        });
+    }
+
+    fn select_projected(
+        &mut self,
+        instances: &[InstanceData],
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        inside: impl Fn(Vec2) -> bool,
+    ) {
+        let Some(viewport) = camera.logical_viewport_rect() else {
+            return;
+        };
+        let clip_from_world =
+            camera.clip_from_view() * Mat4::from(camera_transform.affine().inverse());
+        for (i, instance) in instances.iter().enumerate() {
+            let clip = clip_from_world * Vec3::from_array(instance.position).extend(1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let ndc = clip.truncate() / clip.w;
+            let point = viewport.min + Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * viewport.size();
+            if inside(point) {
+                self.insert(i);
+            }
+        }
+    }
+
+    /// Copies of the selected instances, e.g. for saving them with `save_ply`.
+    pub fn selected_instances(&self, instances: &[InstanceData]) -> Vec<InstanceData> {
+        self.iter()
+            .take_while(|&i| i < instances.len())
+            .map(|i| instances[i])
+            .collect()
+    }
+
+    /// Sets the color of all selected instances.
+    pub fn recolor(&self, instances: &mut [InstanceData], color: Color) {
+        let color = color.to_linear().to_f32_array();
+        let len = instances.len();
+        for i in self.iter().take_while(|&i| i < len) {
+            instances[i].color = color;
+        }
+    }
+
+    /// Removes the selected entries from a per-instance vector, such as `VoxelValues`.
+    pub fn retain_unselected<T>(&self, items: &mut Vec<T>) {
+        let mut index = 0;
+        items.retain(|_| {
+            index += 1;
+            !self.contains(index - 1)
+        });
+    }
+
+    /// Removes the selected instances and clears the selection.
+    pub fn delete(&mut self, instances: &mut Vec<InstanceData>) {
+        self.retain_unselected(instances);
+        self.clear();
+    }
+}
+
+/// Even-odd test of a point against a polygon.
+fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(&last) => last,
+        None => return false,
+    };
+    for &current in polygon {
+        if (current.y > point.y) != (previous.y > point.y)
+            && point.x
+                < (previous.x - current.x) * (point.y - current.y) / (previous.y - current.y)
+                    + current.x
+        {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+/// Per-instance flags of the drawn instances, bit 0 marking selected instances and bits 1 to 27
+/// the occupied neighbours for ambient occlusion.
+#[derive(Component)]
+pub(crate) struct InstanceFlags(pub(crate) Vec<u32>);
+
+/// Extracts the selection flags and occupied neighbours of the drawn instances.
+#[allow(clippy::type_complexity)]
+pub(crate) fn extract_instance_flags(
+    mut commands: Commands,
+    plots: Extract<
+        Query<(
+            RenderEntity,
+            Ref<InstanceMaterialData>,
+            Option<Ref<ReducedInstances>>,
+            Option<Ref<VoxelSelection>>,
+            Option<Ref<VoxelAmbientOcclusion>>,
+        )>,
+    >,
+    extracted: Query<(), With<InstanceFlags>>,
+) {
+    for (entity, instance_data, reduced, selection, occlusion) in &plots {
+        if selection.is_none() && occlusion.is_none() {
+            if extracted.contains(entity) {
+                commands.entity(entity).remove::<InstanceFlags>();
+            }
+            continue;
+        }
+        let changed = instance_data.is_changed()
+            || selection
+                .as_ref()
+                .is_some_and(|selection| selection.is_changed())
+            || occlusion
+                .as_ref()
+                .is_some_and(|occlusion| occlusion.is_changed())
+            || reduced.as_ref().is_some_and(|reduced| reduced.is_changed())
+            || !extracted.contains(entity);
+        if !changed {
+            continue;
+        }
+        let flag = |index: usize| {
+            let selected = selection
+                .as_ref()
+                .is_some_and(|selection| selection.contains(index));
+            let neighbours = occlusion
+                .as_ref()
+                .map_or(0, |occlusion| occlusion.neighbours(index));
+            selected as u32 | neighbours << 1
+        };
+        let flags = match reduced {
+            Some(reduced) => reduced.indices.iter().map(|&i| flag(i as usize)).collect(),
+            None => (0..instance_data.instances.len()).map(flag).collect(),
+        };
+        commands.entity(entity).insert(InstanceFlags(flags));
+    }
+}
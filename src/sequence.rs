@@ -0,0 +1,167 @@
+//! Frame sequences rendered offscreen, e.g. turntable animations of a plot.
+
+use crate::{OffscreenRender, VoxelPlotCamera};
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use std::path::PathBuf;
+
+/// View of a `VoxelPlotCamera` at a point in time of a `CameraPath::Keyframes` path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraKeyframe {
+    /// Time in seconds from the start of the sequence.
+    pub time: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub radius: f32,
+}
+
+/// Movement of the camera during a `FrameSequence`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CameraPath {
+    /// Keeps the camera still, e.g. for playing back a time series.
+    Fixed,
+    /// Orbits around the plot's centre at the camera's pitch and distance, making the given
+    /// number of turns over the sequence. The last frame stops short of the first, so a whole
+    /// number of turns loops seamlessly.
+    Turntable { turns: f32 },
+    /// Linearly interpolates between keyframes sorted by time, holding the first and last view.
+    Keyframes(Vec<CameraKeyframe>),
+}
+
+/// Renders a numbered sequence of frames with the `OffscreenRender` and `VoxelPlotCamera` of the
+/// same camera, moving the camera along a path.
+///
+/// Frames are advanced after each capture rather than by wall-clock time, so sequences are
+/// deterministic however long rendering takes. The frame's time in seconds is `time`, which
+/// animations such as time series can follow. Frame `i` is saved to
+/// `directory/{name}_{i:04}.png`. Set `OffscreenRender::exit_when_done` to exit after the last
+/// frame.
+#[derive(Component, Clone, Debug)]
+pub struct FrameSequence {
+    pub frames: u32,
+    /// Frames per second of the animation.
+    pub fps: f32,
+    pub path: CameraPath,
+    pub directory: PathBuf,
+    pub name: String,
+    /// Frame being rendered, `None` before the first frame.
+    frame: Option<u32>,
+    /// Yaw of the camera at the start of the sequence.
+    start_yaw: f32,
+}
+
+impl FrameSequence {
+    pub fn new(frames: u32, fps: f32, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            frames,
+            fps,
+            path: CameraPath::Fixed,
+            directory: directory.into(),
+            name: "frame".into(),
+            frame: None,
+            start_yaw: 0.0,
+        }
+    }
+
+    /// A full turn around the plot.
+    pub fn turntable(frames: u32, fps: f32, directory: impl Into<PathBuf>) -> Self {
+        Self::new(frames, fps, directory).with_path(CameraPath::Turntable { turns: 1.0 })
+    }
+
+    pub fn with_path(mut self, path: CameraPath) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Index of the frame being rendered.
+    pub fn frame(&self) -> u32 {
+        self.frame.unwrap_or(0)
+    }
+
+    /// Time of the frame being rendered in seconds.
+    pub fn time(&self) -> f32 {
+        self.frame() as f32 / self.fps.max(f32::EPSILON)
+    }
+
+    /// Whether every frame has been rendered.
+    pub fn is_done(&self) -> bool {
+        self.frame.is_some_and(|frame| frame >= self.frames)
+    }
+
+    /// File the frame at `index` is saved to.
+    pub fn frame_path(&self, index: u32) -> PathBuf {
+        self.directory.join(format!("{}_{index:04}.png", self.name))
+    }
+
+    /// Moves the camera to the view of the current frame.
+    fn apply(&self, camera: &mut VoxelPlotCamera) {
+        match &self.path {
+            CameraPath::Fixed => {}
+            CameraPath::Turntable { turns } => {
+                let t = self.frame() as f32 / self.frames.max(1) as f32;
+                camera.yaw = self.start_yaw + turns * TAU * t;
+            }
+            CameraPath::Keyframes(keyframes) => {
+                let time = self.time();
+                let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+                let keyframe = match (next.checked_sub(1), keyframes.get(next)) {
+                    (Some(previous), Some(next)) => {
+                        let previous = &keyframes[previous];
+                        let t = (time - previous.time) / (next.time - previous.time);
+                        CameraKeyframe {
+                            time,
+                            yaw: previous.yaw.lerp(next.yaw, t),
+                            pitch: previous.pitch.lerp(next.pitch, t),
+                            radius: previous.radius.lerp(next.radius, t),
+                        }
+                    }
+                    (Some(previous), None) => keyframes[previous],
+                    (None, Some(first)) => *first,
+                    (None, None) => return,
+                };
+                camera.yaw = keyframe.yaw;
+                camera.pitch = keyframe.pitch;
+                camera.radius = keyframe.radius;
+            }
+        }
+    }
+}
+
+/// Moves the camera and requests the capture of the next frame once the previous one was read
+/// back.
+pub(crate) fn update_frame_sequences(
+    mut sequences: Query<(
+        &mut FrameSequence,
+        &mut OffscreenRender,
+        &mut VoxelPlotCamera,
+    )>,
+) {
+    for (mut sequence, mut render, mut camera) in &mut sequences {
+        let next = match sequence.frame {
+            None => {
+                if let Err(error) = std::fs::create_dir_all(&sequence.directory) {
+                    error!(
+                        "Cannot create {} for the frame sequence: {error}",
+                        sequence.directory.display()
+                    );
+                }
+                sequence.start_yaw = camera.yaw;
+                0
+            }
+            Some(frame) if frame < sequence.frames && render.is_done() => frame + 1,
+            Some(_) => continue,
+        };
+        sequence.frame = Some(next);
+        if next >= sequence.frames {
+            continue;
+        }
+        sequence.apply(&mut camera);
+        render.path = Some(sequence.frame_path(next));
+        render.capture();
+    }
+}
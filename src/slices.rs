@@ -0,0 +1,336 @@
+//! Orthogonal slice images through a dense volume, optionally highlighted in the 3D view.
+
+use crate::{NrrdVolume, VolumeMapping, WindowLevel};
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use std::sync::Arc;
+
+/// Orientation of a slice, named after the two axes it spans.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SlicePlane {
+    /// Spans x and y at an index along z.
+    #[default]
+    XY,
+    /// Spans x and z at an index along y.
+    XZ,
+    /// Spans y and z at an index along x.
+    YZ,
+}
+
+impl SlicePlane {
+    pub const ALL: [SlicePlane; 3] = [SlicePlane::XY, SlicePlane::XZ, SlicePlane::YZ];
+
+    /// Volume axes along the image columns and rows, and the axis the slice is indexed along.
+    pub fn axes(self) -> (usize, usize, usize) {
+        match self {
+            SlicePlane::XY => (0, 1, 2),
+            SlicePlane::XZ => (0, 2, 1),
+            SlicePlane::YZ => (1, 2, 0),
+        }
+    }
+}
+
+impl NrrdVolume {
+    /// Volume from dense grid data, e.g. the data a plot was built from, with `sizes[0]`
+    /// samples along the fastest axis. Samples are one unit apart from the origin, set
+    /// `directions` and `origin` to place the grid in the world.
+    ///
+    /// # Panics
+    ///
+    /// If `data` does not hold a sample for every grid point.
+    pub fn from_grid(sizes: [usize; 3], data: Vec<f32>) -> Self {
+        assert_eq!(
+            Some(data.len()),
+            sizes
+                .iter()
+                .try_fold(1usize, |count, &size| count.checked_mul(size)),
+            "grid data does not match sizes {sizes:?}"
+        );
+        Self {
+            sizes,
+            directions: [Vec3::X, Vec3::Y, Vec3::Z],
+            origin: Vec3::ZERO,
+            data,
+        }
+    }
+
+    /// Whether the volume has samples for all of its `sizes` and none of them is zero.
+    fn is_complete(&self) -> bool {
+        self.sizes
+            .iter()
+            .try_fold(1usize, |count, &size| count.checked_mul(size))
+            .is_some_and(|count| count > 0 && count <= self.data.len())
+    }
+
+    /// Renders the slice at `index` along the plane's normal axis into an opaque image, using
+    /// the window and colormap of the mapping.
+    ///
+    /// Image columns run along the first axis of the plane, rows along the second axis with
+    /// the highest index at the top. The index is clamped to the volume. Empty volumes give a
+    /// single transparent pixel.
+    pub fn slice_image(&self, plane: SlicePlane, index: usize, mapping: &VolumeMapping) -> Image {
+        if !self.is_complete() {
+            return Image::transparent();
+        }
+        let window = mapping.window.unwrap_or_else(|| {
+            let (min, max) = self.range();
+            WindowLevel::from_range(min, max)
+        });
+        let (u_axis, v_axis, n_axis) = plane.axes();
+        let (width, height) = (self.sizes[u_axis], self.sizes[v_axis]);
+        let index = index.min(self.sizes[n_axis].saturating_sub(1));
+
+        let mut data = Vec::with_capacity(width * height * 4);
+        for row in 0..height {
+            for column in 0..width {
+                let mut ijk = [0; 3];
+                ijk[u_axis] = column;
+                ijk[v_axis] = height - 1 - row;
+                ijk[n_axis] = index;
+                let t = window.normalize(self.value(ijk[0], ijk[1], ijk[2]));
+                data.extend(mapping.colormap.sample(t).to_srgba().to_u8_array());
+            }
+        }
+
+        let mut image = Image::new(
+            Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        image.sampler = ImageSampler::nearest();
+        image
+    }
+
+    /// Transform of a unit `Rectangle` covering the slice at `index` in world space.
+    pub fn slice_transform(&self, plane: SlicePlane, index: usize) -> Transform {
+        let (u_axis, v_axis, n_axis) = plane.axes();
+        let index = index.min(self.sizes[n_axis].saturating_sub(1));
+        let mut center = [0.0; 3];
+        center[u_axis] = (self.sizes[u_axis] as f32 - 1.0) * 0.5;
+        center[v_axis] = (self.sizes[v_axis] as f32 - 1.0) * 0.5;
+        center[n_axis] = index as f32;
+
+        let u = self.directions[u_axis] * self.sizes[u_axis] as f32;
+        let v = self.directions[v_axis] * self.sizes[v_axis] as f32;
+        Transform::from_matrix(Mat4::from_cols(
+            u.extend(0.0),
+            v.extend(0.0),
+            u.cross(v).normalize_or_zero().extend(0.0),
+            (self.origin
+                + self.directions[0] * center[0]
+                + self.directions[1] * center[1]
+                + self.directions[2] * center[2])
+                .extend(1.0),
+        ))
+    }
+}
+
+/// Generates XY, XZ and YZ slice images of a volume into `SliceImages`.
+///
+/// The images are regenerated whenever this component changes, e.g. when a slice index is moved.
+/// The highlighted slice is drawn as a textured quad in the 3D view at its position in the
+/// volume, so it follows the index.
+#[derive(Component, Clone, Debug)]
+pub struct VoxelSlices {
+    /// Dense volume the slices are taken from, usually the one the plot was built from, see
+    /// `NrrdVolume::from_grid` for plots not loaded from NRRD files.
+    pub volume: Arc<NrrdVolume>,
+    pub mapping: VolumeMapping,
+    /// Slice index along x, y and z, i.e. of the YZ, XZ and XY slice.
+    pub indices: [usize; 3],
+    /// Slice shown in the 3D view, if any.
+    pub highlight: Option<SlicePlane>,
+    /// Opacity of the highlighted slice in the 3D view.
+    pub highlight_opacity: f32,
+}
+
+impl VoxelSlices {
+    /// Slices through the center of the volume, without a highlighted slice.
+    pub fn new(volume: Arc<NrrdVolume>, mapping: VolumeMapping) -> Self {
+        let indices = volume.sizes.map(|size| size / 2);
+        Self {
+            volume,
+            mapping,
+            indices,
+            highlight: None,
+            highlight_opacity: 0.8,
+        }
+    }
+
+    pub fn with_highlight(mut self, plane: SlicePlane) -> Self {
+        self.highlight = Some(plane);
+        self
+    }
+
+    /// Index of the slice along the normal axis of the plane.
+    pub fn index(&self, plane: SlicePlane) -> usize {
+        self.indices[plane.axes().2]
+    }
+
+    pub fn set_index(&mut self, plane: SlicePlane, index: usize) {
+        self.indices[plane.axes().2] = index;
+    }
+}
+
+/// Slice images of a plot with `VoxelSlices`, e.g. for showing them in egui.
+#[derive(Component, Clone, Debug)]
+pub struct SliceImages {
+    pub xy: Handle<Image>,
+    pub xz: Handle<Image>,
+    pub yz: Handle<Image>,
+    /// Quad and material of the highlighted slice.
+    highlight: Option<(Entity, Handle<StandardMaterial>)>,
+}
+
+impl SliceImages {
+    pub fn get(&self, plane: SlicePlane) -> &Handle<Image> {
+        match plane {
+            SlicePlane::XY => &self.xy,
+            SlicePlane::XZ => &self.xz,
+            SlicePlane::YZ => &self.yz,
+        }
+    }
+}
+
+/// Quad highlighting a slice of the plot entity, spawned in world space like the voxels rather
+/// than as a child, since voxels ignore the plot's transform.
+#[derive(Component)]
+pub(crate) struct SliceHighlight(Entity);
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn update_voxel_slices(
+    mut commands: Commands,
+    plots: Query<(Entity, Ref<VoxelSlices>, Option<&SliceImages>)>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut removed: RemovedComponents<VoxelSlices>,
+    stale: Query<(), (With<SliceImages>, Without<VoxelSlices>)>,
+    highlights: Query<(Entity, &SliceHighlight)>,
+) {
+    for entity in removed.read() {
+        // Also reached when the plot is despawned, which leaves its highlight behind.
+        for (quad, highlight) in &highlights {
+            if highlight.0 == entity {
+                commands.entity(quad).despawn();
+            }
+        }
+        if stale.contains(entity) {
+            commands.entity(entity).remove::<SliceImages>();
+        }
+    }
+
+    for (entity, slices, slice_images) in &plots {
+        if !slices.is_changed() && slice_images.is_some() {
+            continue;
+        }
+        let [xy, xz, yz] = SlicePlane::ALL.map(|plane| {
+            slices
+                .volume
+                .slice_image(plane, slices.index(plane), &slices.mapping)
+        });
+        let mut slice_images = match slice_images {
+            Some(slice_images) => {
+                for (handle, image) in [
+                    (&slice_images.xy, xy),
+                    (&slice_images.xz, xz),
+                    (&slice_images.yz, yz),
+                ] {
+                    if let Some(target) = images.get_mut(handle) {
+                        *target = image;
+                    }
+                }
+                slice_images.clone()
+            }
+            None => SliceImages {
+                xy: images.add(xy),
+                xz: images.add(xz),
+                yz: images.add(yz),
+                highlight: None,
+            },
+        };
+
+        slice_images.highlight = match (slices.highlight, slice_images.highlight.take()) {
+            (Some(plane), highlight) => {
+                let transform = slices.volume.slice_transform(plane, slices.index(plane));
+                let material = StandardMaterial {
+                    base_color: Color::WHITE.with_alpha(slices.highlight_opacity),
+                    base_color_texture: Some(slice_images.get(plane).clone()),
+                    unlit: true,
+                    alpha_mode: AlphaMode::Blend,
+                    cull_mode: None,
+                    double_sided: true,
+                    ..default()
+                };
+                match highlight {
+                    Some((quad, handle)) => {
+                        // Replacing the material also picks up the regenerated image.
+                        if let Some(target) = materials.get_mut(&handle) {
+                            *target = material;
+                        }
+                        commands.entity(quad).insert(transform);
+                        Some((quad, handle))
+                    }
+                    None => {
+                        let handle = materials.add(material);
+                        let quad = commands
+                            .spawn((
+                                Mesh3d(meshes.add(Rectangle::new(1.0, 1.0))),
+                                MeshMaterial3d(handle.clone()),
+                                transform,
+                                SliceHighlight(entity),
+                            ))
+                            .id();
+                        Some((quad, handle))
+                    }
+                }
+            }
+            (None, Some((quad, _))) => {
+                commands.entity(quad).despawn();
+                None
+            }
+            (None, None) => None,
+        };
+        commands.entity(entity).insert(slice_images);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Colormap;
+
+    #[test]
+    fn grid_slices() {
+        let volume = NrrdVolume::from_grid([2, 3, 4], (0..24).map(|v| v as f32).collect());
+        let image = volume.slice_image(SlicePlane::XZ, 10, &VolumeMapping::default());
+        assert_eq!(image.size(), UVec2::new(2, 4));
+        // The top left pixel is x = 0 at the highest z, clamped y = 2: value 22 of 0 to 23.
+        let top_left = &image.data.as_ref().unwrap()[..4];
+        let expected = Colormap::Grayscale
+            .sample(22.0 / 23.0)
+            .to_srgba()
+            .to_u8_array();
+        assert_eq!(top_left, expected);
+    }
+
+    #[test]
+    fn empty_volumes() {
+        let mut volume = NrrdVolume::from_grid([4, 4, 0], Vec::new());
+        for plane in SlicePlane::ALL {
+            let image = volume.slice_image(plane, 0, &VolumeMapping::default());
+            assert_eq!(image.size(), UVec2::ONE);
+        }
+        volume.sizes = [4, 4, 4];
+        let image = volume.slice_image(SlicePlane::XY, 0, &VolumeMapping::default());
+        assert_eq!(image.size(), UVec2::ONE);
+    }
+}
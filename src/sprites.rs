@@ -0,0 +1,78 @@
+//! Point sprite rendering: every instance as a camera facing quad instead of a mesh.
+
+use bevy::asset::uuid_handle;
+use bevy::prelude::*;
+use bevy::render::extract_component::ExtractComponent;
+
+/// Unit quad the sprites are drawn with.
+pub(crate) const SPRITE_MESH: Handle<Mesh> = uuid_handle!("6a0f3c1e-4b2d-4e8f-9a7c-2d5b8e1f0c34");
+
+/// Sprite mode bits of the plot uniform, mirrored in the shader.
+pub(crate) const SPRITE_ENABLED: u32 = 1;
+pub(crate) const SPRITE_ROUND: u32 = 2;
+pub(crate) const SPRITE_PIXELS: u32 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpriteShape {
+    /// Discs, discarded outside of the inscribed circle.
+    #[default]
+    Round,
+    Square,
+}
+
+/// Size of a sprite of an instance with scale one, scaled by the instance's scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpriteSize {
+    /// Edge length in world units, sprites get smaller with distance.
+    World(f32),
+    /// Edge length in physical pixels, independent of the distance.
+    Pixels(f32),
+}
+
+/// Draws the instances of a plot as camera facing quads instead of the plot's mesh, which is
+/// much cheaper than cubes for dense point clouds.
+///
+/// Sprites take precedence over `VoxelGlyphs`. Picking and hovering still treat instances as
+/// voxels of the plot's mesh.
+#[derive(Component, ExtractComponent, Clone, Copy, Debug, PartialEq)]
+pub struct VoxelSprites {
+    pub shape: SpriteShape,
+    pub size: SpriteSize,
+}
+
+impl Default for VoxelSprites {
+    fn default() -> Self {
+        Self {
+            shape: SpriteShape::Round,
+            size: SpriteSize::Pixels(4.0),
+        }
+    }
+}
+
+impl VoxelSprites {
+    pub fn world(shape: SpriteShape, size: f32) -> Self {
+        Self {
+            shape,
+            size: SpriteSize::World(size),
+        }
+    }
+
+    pub fn pixels(shape: SpriteShape, size: f32) -> Self {
+        Self {
+            shape,
+            size: SpriteSize::Pixels(size),
+        }
+    }
+
+    /// Mode bits and size for the plot uniform.
+    pub(crate) fn uniform(&self) -> (u32, f32) {
+        let shape = match self.shape {
+            SpriteShape::Round => SPRITE_ROUND,
+            SpriteShape::Square => 0,
+        };
+        match self.size {
+            SpriteSize::World(size) => (SPRITE_ENABLED | shape, size),
+            SpriteSize::Pixels(size) => (SPRITE_ENABLED | shape | SPRITE_PIXELS, size),
+        }
+    }
+}
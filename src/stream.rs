@@ -0,0 +1,227 @@
+//! Append-only voxel plots for live data, backed by a fixed size ring buffer.
+
+use crate::glyphs::GlyphDraw;
+use crate::{create_instance_buffer, InstanceBuffer, InstanceBuffers, InstanceData};
+use bevy::prelude::*;
+use bevy::render::{
+    render_resource::Buffer,
+    renderer::{RenderDevice, RenderQueue},
+    sync_world::RenderEntity,
+    Extract,
+};
+
+/// Component holding a stream of instances with a maximum capacity.
+///
+/// Once the capacity is reached, pushing a new instance evicts the oldest one. Only instances
+/// pushed since the last frame are uploaded to the GPU. Instances are drawn in ring order and
+/// are not depth sorted, so streams work best for opaque or uniformly translucent data.
+///
+/// Use this instead of `InstanceMaterialData` on an entity with a `Mesh3d`.
+#[derive(Component, Clone)]
+pub struct InstanceStream {
+    capacity: usize,
+    ring: Vec<InstanceData>,
+    /// Total number of instances pushed since the last reset.
+    pushed: u64,
+    /// Incremented whenever the ring is cleared, which forces a full upload.
+    generation: u32,
+}
+
+impl InstanceStream {
+    /// Creates an empty stream that holds at most `capacity` instances.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ring: Vec::new(),
+            pushed: 0,
+            generation: 0,
+        }
+    }
+
+    /// Appends an instance, evicting the oldest one if the stream is full.
+    pub fn push(&mut self, instance: InstanceData) {
+        if self.ring.len() < self.capacity {
+            self.ring.push(instance);
+        } else {
+            let slot = (self.pushed % self.capacity as u64) as usize;
+            self.ring[slot] = instance;
+        }
+        self.pushed += 1;
+    }
+
+    /// Removes all instances.
+    pub fn clear(&mut self) {
+        self.ring.clear();
+        self.pushed = 0;
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Maximum number of instances held by the stream.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Index of the oldest instance in the ring.
+    fn oldest_slot(&self) -> usize {
+        if self.ring.len() < self.capacity {
+            0
+        } else {
+            (self.pushed % self.capacity as u64) as usize
+        }
+    }
+
+    /// Iterates over the instances from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &InstanceData> {
+        let (newer, older) = self.ring.split_at(self.oldest_slot());
+        older.iter().chain(newer)
+    }
+}
+
+impl Extend<InstanceData> for InstanceStream {
+    fn extend<T: IntoIterator<Item = InstanceData>>(&mut self, iter: T) {
+        for instance in iter {
+            self.push(instance);
+        }
+    }
+}
+
+/// Instances pushed to a stream since the last upload, extracted to the render world.
+#[derive(Component)]
+pub(crate) struct ExtractedInstanceStream {
+    capacity: usize,
+    generation: u32,
+    pushed: u64,
+    len: usize,
+    /// Contiguous runs of ring slots to upload, as `(first slot, instances)`.
+    writes: Vec<(usize, Vec<InstanceData>)>,
+}
+
+/// GPU ring buffer of a stream.
+#[derive(Component)]
+pub(crate) struct InstanceStreamBuffer {
+    buffer: Buffer,
+    /// Zeroed flags, streams do not support selection.
+    flags: Buffer,
+    capacity: usize,
+    generation: u32,
+    uploaded: u64,
+}
+
+/// Extracts only the instances that have not been uploaded yet.
+pub(crate) fn extract_instance_streams(
+    mut commands: Commands,
+    streams: Extract<Query<(RenderEntity, &InstanceStream)>>,
+    buffers: Query<&InstanceStreamBuffer>,
+) {
+    for (entity, stream) in &streams {
+        let uploaded = buffers
+            .get(entity)
+            .ok()
+            .filter(|b| b.capacity == stream.capacity && b.generation == stream.generation)
+            .map(|b| b.uploaded);
+
+        let new = match uploaded {
+            Some(uploaded) => (stream.pushed - uploaded).min(stream.len() as u64) as usize,
+            None => stream.len(),
+        };
+
+        let mut writes = Vec::new();
+        if new > 0 {
+            let end = if stream.len() < stream.capacity {
+                stream.len()
+            } else {
+                stream.oldest_slot()
+            };
+            let start = (end + stream.len() - new) % stream.len();
+            if start < end {
+                writes.push((start, stream.ring[start..end].to_vec()));
+            } else {
+                writes.push((start, stream.ring[start..].to_vec()));
+                if end > 0 {
+                    writes.push((0, stream.ring[..end].to_vec()));
+                }
+            }
+        }
+
+        commands.entity(entity).insert(ExtractedInstanceStream {
+            capacity: stream.capacity,
+            generation: stream.generation,
+            pushed: stream.pushed,
+            len: stream.len(),
+            writes,
+        });
+    }
+}
+
+/// Writes new stream instances into the GPU ring buffer.
+pub(crate) fn prepare_instance_streams(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut query: Query<(
+        Entity,
+        &ExtractedInstanceStream,
+        Option<&mut InstanceStreamBuffer>,
+    )>,
+) {
+    for (entity, stream, stream_buffer) in &mut query {
+        let (buffer, flags) = match stream_buffer {
+            Some(mut stream_buffer)
+                if stream_buffer.capacity == stream.capacity
+                    && stream_buffer.generation == stream.generation =>
+            {
+                stream_buffer.uploaded = stream.pushed;
+                (stream_buffer.buffer.clone(), stream_buffer.flags.clone())
+            }
+            _ => {
+                // Extraction already handed over the whole ring for a missing or stale buffer.
+                let buffer = create_instance_buffer::<InstanceData>(
+                    &render_device,
+                    "instance stream buffer",
+                    stream.capacity,
+                );
+                let flags = create_instance_buffer::<u32>(
+                    &render_device,
+                    "instance stream flags buffer",
+                    stream.capacity,
+                );
+                commands.entity(entity).insert(InstanceStreamBuffer {
+                    buffer: buffer.clone(),
+                    flags: flags.clone(),
+                    capacity: stream.capacity,
+                    generation: stream.generation,
+                    uploaded: stream.pushed,
+                });
+                (buffer, flags)
+            }
+        };
+
+        for (slot, instances) in &stream.writes {
+            render_queue.write_buffer(
+                &buffer,
+                (slot * size_of::<InstanceData>()) as u64,
+                bytemuck::cast_slice(instances),
+            );
+        }
+
+        if stream.len == 0 {
+            commands.entity(entity).remove::<InstanceBuffers>();
+        } else {
+            commands
+                .entity(entity)
+                .insert(InstanceBuffers(vec![InstanceBuffer {
+                    buffer,
+                    flags,
+                    draws: GlyphDraw::plot_mesh(stream.len),
+                }]));
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Playback of a plot from a series of voxel frames, e.g. one per simulation timestep.
+
+use crate::{FrameSequence, InstanceCache, InstanceData, InstanceMaterialData};
+use bevy::asset::AssetPath;
+use bevy::prelude::*;
+use std::sync::Arc;
+
+/// A frame of a `VoxelTimeSeries`.
+#[derive(Clone, Debug)]
+pub enum VoxelFrame {
+    Instances(Arc<Vec<InstanceData>>),
+    /// Instance cache asset (`.vxp`), loaded when the frame is about to be shown and released
+    /// once it is no longer needed.
+    Asset(AssetPath<'static>),
+}
+
+impl From<Vec<InstanceData>> for VoxelFrame {
+    fn from(instances: Vec<InstanceData>) -> Self {
+        VoxelFrame::Instances(Arc::new(instances))
+    }
+}
+
+/// Plays back a series of frames on a plot, uploading only the shown frame's instances into its
+/// `InstanceMaterialData`.
+///
+/// With `interpolate`, colors and opacities are blended between consecutive frames of the same
+/// length for smooth playback, positions and scales are taken from the earlier frame. Playback
+/// follows the app's time, or the frame time of a camera's `FrameSequence` if `sequence` is set,
+/// for deterministic exports. Captures of that camera wait until the frame of the sequence's
+/// time is shown, see `is_ready`.
+#[derive(Component, Clone, Debug)]
+pub struct VoxelTimeSeries {
+    pub frames: Vec<VoxelFrame>,
+    /// Frames per second at a rate of one.
+    pub fps: f32,
+    /// Playback speed, negative values play backwards.
+    pub rate: f32,
+    pub playing: bool,
+    /// Whether playback wraps around at the ends instead of stopping.
+    pub looping: bool,
+    pub interpolate: bool,
+    /// Camera whose `FrameSequence` drives playback instead of the app's time.
+    pub sequence: Option<Entity>,
+    /// Playback position in frames.
+    position: f32,
+    /// Frames and blend factor last uploaded.
+    shown: Option<(usize, usize, f32)>,
+    /// Whether the frames at `position` are shown.
+    ready: bool,
+    /// Loaded frame assets by frame index.
+    handles: Vec<(usize, Handle<InstanceCache>)>,
+}
+
+impl VoxelTimeSeries {
+    /// Paused series showing the first frame.
+    pub fn new(frames: impl IntoIterator<Item = VoxelFrame>, fps: f32) -> Self {
+        Self {
+            frames: frames.into_iter().collect(),
+            fps,
+            rate: 1.0,
+            playing: false,
+            looping: true,
+            interpolate: false,
+            sequence: None,
+            position: 0.0,
+            shown: None,
+            ready: false,
+            handles: Vec::new(),
+        }
+    }
+
+    /// Series of instance cache files, loaded lazily.
+    pub fn from_assets(
+        paths: impl IntoIterator<Item = impl Into<AssetPath<'static>>>,
+        fps: f32,
+    ) -> Self {
+        Self::new(
+            paths.into_iter().map(|path| VoxelFrame::Asset(path.into())),
+            fps,
+        )
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_interpolation(mut self) -> Self {
+        self.interpolate = true;
+        self
+    }
+
+    pub fn with_sequence(mut self, camera: Entity) -> Self {
+        self.sequence = Some(camera);
+        self
+    }
+
+    pub fn playing(mut self) -> Self {
+        self.playing = true;
+        self
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+    }
+
+    /// Pauses and moves by a number of frames.
+    pub fn step(&mut self, frames: i32) {
+        self.playing = false;
+        self.seek(self.position.floor() + frames as f32);
+    }
+
+    /// Moves to a position in frames, wrapped or clamped to the series.
+    pub fn seek(&mut self, position: f32) {
+        self.position = self.wrap(position);
+    }
+
+    fn wrap(&self, position: f32) -> f32 {
+        let len = self.frames.len().max(1) as f32;
+        if self.looping {
+            position.rem_euclid(len)
+        } else {
+            position.clamp(0.0, len - 1.0)
+        }
+    }
+
+    /// Whether the frame at the playback position is shown, which it is not while its asset
+    /// loads.
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Whether the frame of the time of `sequence` is shown, if playback follows it.
+    pub(crate) fn is_ready_for(&self, sequence: &FrameSequence) -> bool {
+        let position = self.wrap(sequence.time() * self.fps * self.rate);
+        self.ready && (!self.playing || self.position == position)
+    }
+
+    /// Playback position in frames.
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Index of the shown frame.
+    pub fn frame(&self) -> usize {
+        self.position as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Advances the position by `frames`, stopping at the ends unless looping.
+    fn advance(&mut self, frames: f32) {
+        let len = self.frames.len() as f32;
+        let position = self.position + frames;
+        if !self.looping && !(0.0..=len - 1.0).contains(&position) {
+            self.playing = false;
+        }
+        self.seek(position);
+    }
+
+    /// Frame following `frame` in playback order.
+    fn next(&self, frame: usize) -> usize {
+        if self.looping {
+            (frame + 1) % self.frames.len()
+        } else {
+            (frame + 1).min(self.frames.len() - 1)
+        }
+    }
+}
+
+/// Instances of a frame, if they are available.
+fn frame_instances<'a>(
+    series: &'a VoxelTimeSeries,
+    frame: usize,
+    caches: &'a Assets<InstanceCache>,
+) -> Option<&'a [InstanceData]> {
+    match &series.frames[frame] {
+        VoxelFrame::Instances(instances) => Some(instances),
+        VoxelFrame::Asset(_) => series
+            .handles
+            .iter()
+            .find(|(index, _)| *index == frame)
+            .and_then(|(_, handle)| caches.get(handle))
+            .map(|cache| cache.instances.as_slice()),
+    }
+}
+
+/// Blends color and opacity of two frames of the same layout.
+fn blend(from: &[InstanceData], to: &[InstanceData], t: f32) -> Vec<InstanceData> {
+    from.iter()
+        .zip(to)
+        .map(|(from, to)| InstanceData {
+            color: std::array::from_fn(|i| from.color[i] + (to.color[i] - from.color[i]) * t),
+            ..*from
+        })
+        .collect()
+}
+
+/// Advances playback, loads the frame assets around the position and uploads the shown frame.
+pub(crate) fn update_voxel_time_series(
+    mut commands: Commands,
+    mut plots: Query<(
+        Entity,
+        &mut VoxelTimeSeries,
+        Option<&mut InstanceMaterialData>,
+    )>,
+    sequences: Query<&FrameSequence>,
+    asset_server: Res<AssetServer>,
+    caches: Res<Assets<InstanceCache>>,
+    time: Res<Time>,
+) {
+    for (entity, mut series, instance_data) in &mut plots {
+        if series.frames.is_empty() {
+            series.bypass_change_detection().ready = true;
+            continue;
+        }
+        if series.playing {
+            match series
+                .sequence
+                .and_then(|camera| sequences.get(camera).ok())
+            {
+                Some(sequence) => {
+                    let position = sequence.time() * series.fps * series.rate;
+                    series.seek(position);
+                }
+                None => {
+                    let frames = time.delta_secs() * series.fps * series.rate;
+                    series.advance(frames);
+                }
+            }
+        }
+
+        let frame = series.frame().min(series.frames.len() - 1);
+        let next = series.next(frame);
+        let t = if series.interpolate {
+            series.position.fract()
+        } else {
+            0.0
+        };
+
+        // Keep the assets of the shown and the following frame loaded.
+        let needed = [frame, next];
+        let series = series.bypass_change_detection();
+        series.handles.retain(|(index, _)| needed.contains(index));
+        for index in needed {
+            if let VoxelFrame::Asset(path) = &series.frames[index] {
+                if !series.handles.iter().any(|(loaded, _)| *loaded == index) {
+                    let handle = asset_server.load(path.clone());
+                    series.handles.push((index, handle));
+                }
+            }
+        }
+
+        let Some(from) = frame_instances(series, frame, &caches) else {
+            series.ready = false;
+            continue;
+        };
+        let to = frame_instances(series, next, &caches);
+        let to = match to {
+            Some(to) if t > 0.0 && next != frame && to.len() == from.len() => Some(to),
+            // Wait for the next frame to blend towards, unless nothing is shown yet.
+            None if t > 0.0 && next != frame && series.shown.is_some() => {
+                series.ready = false;
+                continue;
+            }
+            _ => None,
+        };
+        let t = if to.is_some() { t } else { 0.0 };
+        let instances = (series.shown != Some((frame, next, t))).then(|| match to {
+            Some(to) => blend(from, to, t),
+            None => from.to_vec(),
+        });
+        series.ready = true;
+        let Some(instances) = instances else {
+            continue;
+        };
+        series.shown = Some((frame, next, t));
+        match instance_data {
+            Some(mut instance_data) => instance_data.instances = instances,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(InstanceMaterialData { instances });
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Per-plot shader parameters, bound at group 3 of the voxel pipeline.
+
+use crate::lod::mesh_edge;
+use crate::{
+    HoveredVoxel, InstanceMaterialData, InstanceStream, VoxelAmbientOcclusion, VoxelClip,
+    VoxelHover, VoxelHoverHighlight, VoxelPixelSize, VoxelSelection, VoxelSprites, MAX_CLIP_PLANES,
+};
+use bevy::ecs::system::{lifetimeless::SRes, SystemParamItem};
+use bevy::prelude::*;
+use bevy::render::{
+    extract_component::{ComponentUniforms, DynamicUniformIndex},
+    render_phase::{PhaseItem, RenderCommand, RenderCommandResult, TrackedRenderPass},
+    render_resource::{
+        binding_types::uniform_buffer, BindGroup, BindGroupEntries, BindGroupLayoutDescriptor,
+        BindGroupLayoutEntries, PipelineCache, ShaderStages, ShaderType,
+    },
+    renderer::RenderDevice,
+    sync_world::RenderEntity,
+    Extract,
+};
+
+/// Shader parameters of a plot.
+#[derive(Component, ShaderType, Clone)]
+pub(crate) struct PlotUniform {
+    /// Position and scale of the highlighted instance.
+    highlight_instance: Vec4,
+    /// Color of the highlighted instance, a zero alpha disables the highlight.
+    highlight_color: Vec4,
+    /// Color blended over selected instances.
+    selection_color: Vec4,
+    /// Clipping planes as normal and distance, instances must lie on the positive side.
+    clip_planes: [Vec4; MAX_CLIP_PLANES],
+    /// Center plane of the slab.
+    slab_plane: Vec4,
+    /// Number of used clipping planes.
+    clip_count: u32,
+    /// Half thickness of the slab, negative if slab mode is off.
+    slab_half_thickness: f32,
+    /// Sprite mode bits, zero draws the plot's mesh.
+    sprite_mode: u32,
+    /// Edge length of sprites in world units or pixels.
+    sprite_size: f32,
+    /// Pixel size of instances with scale one, zero keeps world sizes.
+    pixel_size: f32,
+    min_pixels: f32,
+    max_pixels: f32,
+    /// Largest edge of the plot's mesh, zero if voxels aren't sized in pixels.
+    mesh_size: f32,
+    /// Darkening of fully occluded corners, zero disables ambient occlusion.
+    occlusion_strength: f32,
+}
+
+impl Default for PlotUniform {
+    fn default() -> Self {
+        Self {
+            highlight_instance: Vec4::ZERO,
+            highlight_color: Vec4::ZERO,
+            selection_color: Vec4::ZERO,
+            clip_planes: [Vec4::ZERO; MAX_CLIP_PLANES],
+            slab_plane: Vec4::ZERO,
+            clip_count: 0,
+            slab_half_thickness: -1.0,
+            sprite_mode: 0,
+            sprite_size: 0.0,
+            pixel_size: 0.0,
+            min_pixels: 0.0,
+            max_pixels: f32::MAX,
+            mesh_size: 0.0,
+            occlusion_strength: 0.0,
+        }
+    }
+}
+
+pub(crate) fn plot_bind_group_layout() -> BindGroupLayoutDescriptor {
+    BindGroupLayoutDescriptor::new(
+        "plot bind group layout",
+        &BindGroupLayoutEntries::single(
+            ShaderStages::VERTEX_FRAGMENT,
+            uniform_buffer::<PlotUniform>(true),
+        ),
+    )
+}
+
+/// Bind group of the plot uniforms, indexed with a dynamic offset per plot.
+#[derive(Resource)]
+pub(crate) struct PlotBindGroup(BindGroup);
+
+/// Gathers the shader parameters of every plot.
+#[allow(clippy::type_complexity)]
+pub(crate) fn extract_plot_uniforms(
+    mut commands: Commands,
+    plots: Extract<
+        Query<
+            (
+                RenderEntity,
+                Entity,
+                Option<&VoxelHoverHighlight>,
+                Option<&VoxelSelection>,
+                Option<&VoxelClip>,
+                Option<&VoxelSprites>,
+                Option<&VoxelPixelSize>,
+                Option<&Mesh3d>,
+                Option<&VoxelAmbientOcclusion>,
+            ),
+            Or<(With<InstanceMaterialData>, With<InstanceStream>)>,
+        >,
+    >,
+    hover: Extract<Res<VoxelHover>>,
+    meshes: Extract<Res<Assets<Mesh>>>,
+) {
+    for (render_entity, entity, highlight, selection, clip, sprites, pixel_size, mesh, occlusion) in
+        &plots
+    {
+        let mut uniform = PlotUniform::default();
+        if let Some(occlusion) = occlusion {
+            uniform.occlusion_strength = occlusion.strength;
+        }
+        if let Some(sprites) = sprites {
+            (uniform.sprite_mode, uniform.sprite_size) = sprites.uniform();
+        }
+        if let Some(pixel_size) = pixel_size {
+            uniform.pixel_size = pixel_size.size.unwrap_or(0.0);
+            uniform.min_pixels = pixel_size.min;
+            uniform.max_pixels = pixel_size.max;
+            if let Some(mesh) = mesh {
+                uniform.mesh_size = mesh_edge(&meshes, mesh);
+            }
+        }
+        if let Some(clip) = clip {
+            for (slot, plane) in uniform.clip_planes.iter_mut().zip(&clip.planes) {
+                *slot = plane.to_vec4();
+            }
+            uniform.clip_count = clip.planes.len().min(MAX_CLIP_PLANES) as u32;
+            if let Some(slab) = clip.slab.filter(|slab| !slab.plane.is_disabled()) {
+                uniform.slab_plane = slab.plane.to_vec4();
+                uniform.slab_half_thickness = slab.thickness * 0.5;
+            }
+        }
+        if let Some(selection) = selection {
+            uniform.selection_color = selection.color.to_linear().to_vec4();
+        }
+        if let (Some(highlight), Some(hovered)) = (highlight, hover.0) {
+            if hovered.entity == entity {
+                let HoveredVoxel {
+                    position, scale, ..
+                } = hovered;
+                uniform.highlight_instance = position.extend(scale);
+                uniform.highlight_color = highlight.color.to_linear().to_vec4();
+            }
+        }
+        commands.entity(render_entity).insert(uniform);
+    }
+}
+
+pub(crate) fn prepare_plot_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline_cache: Res<PipelineCache>,
+    uniforms: Res<ComponentUniforms<PlotUniform>>,
+) {
+    let Some(binding) = uniforms.uniforms().binding() else {
+        return;
+    };
+    commands.insert_resource(PlotBindGroup(render_device.create_bind_group(
+        "plot bind group",
+        &pipeline_cache.get_bind_group_layout(&plot_bind_group_layout()),
+        &BindGroupEntries::single(binding),
+    )));
+}
+
+/// Binds the uniforms of the drawn plot.
+pub(crate) struct SetPlotBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetPlotBindGroup<I> {
+    type Param = SRes<PlotBindGroup>;
+    type ViewQuery = ();
+    type ItemQuery = &'static DynamicUniformIndex<PlotUniform>;
+
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        index: Option<&'w DynamicUniformIndex<PlotUniform>>,
+        bind_group: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(index) = index else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, &bind_group.into_inner().0, &[index.index()]);
+        RenderCommandResult::Success
+    }
+}